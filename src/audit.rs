@@ -0,0 +1,224 @@
+//! `cask audit`: looks up installed package versions in the OSV advisory database
+//! (osv.dev, which collects the PyPA and GitHub advisories for PyPI).
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::lockfile::Pin;
+use crate::{bootstrap, config};
+
+const OSV_API: &str = "https://api.osv.dev/v1";
+/// OSV accepts at most this many packages per batch query
+const BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Moderate,
+    High,
+    Critical,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "low" => Ok(Severity::Low),
+            "moderate" | "medium" => Ok(Severity::Moderate),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(format!("unknown severity '{}' (expected low, moderate, high or critical)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Low => "low",
+            Severity::Moderate => "moderate",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+/// One advisory affecting one installed package
+#[derive(Debug)]
+pub struct Advisory {
+    /// The CVE when the advisory has one, else its OSV id (GHSA-…, PYSEC-…)
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub summary: Option<String>,
+    /// None when no database rated it
+    pub severity: Option<Severity>,
+    /// Versions the advisory lists as fixed, one per affected release line
+    pub fixed: Vec<String>,
+}
+
+impl Advisory {
+    /// Unrated advisories count against every threshold; better to look twice than ship it
+    pub fn at_least(&self, threshold: Severity) -> bool {
+        self.severity.is_none_or(|severity| severity >= threshold)
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    results: Vec<BatchResult>,
+}
+
+#[derive(Deserialize)]
+struct BatchResult {
+    #[serde(default)]
+    vulns: Vec<VulnRef>,
+}
+
+#[derive(Deserialize)]
+struct VulnRef {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Vuln {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    summary: Option<String>,
+    #[serde(default)]
+    affected: Vec<Affected>,
+    database_specific: Option<DatabaseSpecific>,
+}
+
+#[derive(Deserialize)]
+struct DatabaseSpecific {
+    severity: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Affected {
+    package: Option<AffectedPackage>,
+    #[serde(default)]
+    ranges: Vec<Range>,
+}
+
+#[derive(Deserialize)]
+struct AffectedPackage {
+    name: String,
+    ecosystem: String,
+}
+
+#[derive(Deserialize)]
+struct Range {
+    #[serde(default)]
+    events: Vec<RangeEvent>,
+}
+
+#[derive(Deserialize)]
+struct RangeEvent {
+    fixed: Option<String>,
+}
+
+/// Every known advisory for the pinned packages. Direct references and editables have
+/// no version to look up and are skipped.
+pub fn scan(pins: &BTreeMap<String, Pin>) -> Result<Vec<Advisory>> {
+    if bootstrap::offline() {
+        anyhow::bail!("Auditing needs the OSV advisory database, but offline mode is on");
+    }
+    let packages: Vec<(&String, &String)> =
+        pins.iter().filter_map(|(name, pin)| Some((name, pin.version.as_ref()?))).collect();
+    let client = bootstrap::http_client()?;
+
+    let mut advisories = Vec::new();
+    let mut details: BTreeMap<String, Vuln> = BTreeMap::new();
+    for batch in packages.chunks(BATCH_SIZE) {
+        let queries: Vec<_> = batch
+            .iter()
+            .map(|(name, version)| json!({ "package": { "name": name, "ecosystem": "PyPI" }, "version": version }))
+            .collect();
+        let response = client
+            .post(format!("{}/querybatch", OSV_API))
+            .header("Content-Type", "application/json")
+            .body(json!({ "queries": queries }).to_string())
+            .send()
+            .and_then(|r| r.error_for_status())
+            .context("Failed to query the OSV advisory database")?;
+        let response: BatchResponse = serde_json::from_reader(response).context("Unexpected response from OSV")?;
+
+        for ((name, version), result) in batch.iter().zip(response.results) {
+            for vuln in result.vulns {
+                if !details.contains_key(&vuln.id) {
+                    details.insert(vuln.id.clone(), fetch(&client, &vuln.id)?);
+                }
+                advisories.push(advisory(&details[&vuln.id], name, version));
+            }
+        }
+    }
+    Ok(dedupe(advisories))
+}
+
+/// The batch endpoint only returns ids; summary, severity and fixes need one lookup each
+fn fetch(client: &reqwest::blocking::Client, id: &str) -> Result<Vuln> {
+    let response = client
+        .get(format!("{}/vulns/{}", OSV_API, id))
+        .send()
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Failed to fetch advisory {}", id))?;
+    serde_json::from_reader(response).with_context(|| format!("Unexpected response from OSV for {}", id))
+}
+
+fn advisory(vuln: &Vuln, package: &str, version: &str) -> Advisory {
+    let fixed = vuln
+        .affected
+        .iter()
+        .filter(|affected| {
+            affected.package.as_ref().is_some_and(|p| {
+                p.ecosystem == "PyPI" && config::requirement_name(&p.name) == package
+            })
+        })
+        .flat_map(|affected| &affected.ranges)
+        .flat_map(|range| &range.events)
+        .filter_map(|event| event.fixed.clone())
+        .collect();
+    Advisory {
+        id: vuln.aliases.iter().find(|alias| alias.starts_with("CVE-")).unwrap_or(&vuln.id).clone(),
+        package: package.to_string(),
+        version: version.to_string(),
+        summary: vuln.summary.clone(),
+        severity: vuln.database_specific.as_ref().and_then(|d| d.severity.as_deref()?.parse().ok()),
+        fixed,
+    }
+}
+
+/// PyPA and GitHub often publish the same CVE; keep one entry, preferring a rated one
+fn dedupe(advisories: Vec<Advisory>) -> Vec<Advisory> {
+    let mut unique: BTreeMap<(String, String), Advisory> = BTreeMap::new();
+    for advisory in advisories {
+        let key = (advisory.package.clone(), advisory.id.clone());
+        match unique.get_mut(&key) {
+            Some(existing) => {
+                if existing.severity.is_none() {
+                    existing.severity = advisory.severity;
+                }
+                if existing.summary.is_none() {
+                    existing.summary = advisory.summary;
+                }
+                for fixed in advisory.fixed {
+                    if !existing.fixed.contains(&fixed) {
+                        existing.fixed.push(fixed);
+                    }
+                }
+            }
+            None => {
+                unique.insert(key, advisory);
+            }
+        }
+    }
+    unique.into_values().collect()
+}
@@ -1,123 +1,642 @@
 use anyhow::{Context, Result};
-use directories::BaseDirs;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
+use std::cell::Cell;
 use std::fs::{self, File};
-use std::io::{self, Read, Write}; // Fixed: Added Read
+use std::io::{self, IsTerminal, Read, Seek, Write};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
 
-const UV_VERSION: &str = "0.9.28";
+use crate::holotree;
+use crate::status;
+use crate::ui;
+
+/// Engine version used when a blueprint doesn't pin `engine_version`
+pub const UV_VERSION: &str = "0.9.28";
 const BASE_URL: &str = "https://github.com/astral-sh/uv/releases/download";
+/// The only hosts an official download may touch; GitHub redirects assets to its CDN
+const RELEASE_HOSTS: &[&str] = &["github.com", "objects.githubusercontent.com", "release-assets.githubusercontent.com"];
+const DEFAULT_DOWNLOAD_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Connect timeout, and the longest a download may go without receiving data
+const DEFAULT_NET_TIMEOUT: Duration = Duration::from_secs(30);
+/// Written by `cask upgrade`; overrides UV_VERSION for blueprints without a pin
+const DEFAULT_VERSION_FILE: &str = "default-version";
+
+/// Set once from `--offline` / CASK_OFFLINE; forbids every download
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+pub fn offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Set once from `--uv-path` / CASK_UV_PATH: a user-managed uv used instead of bootstrapping
+static UV_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Points cask at an existing uv. `path` (from `--uv-path`) wins over CASK_UV_PATH; a bare
+/// name like "uv" is looked up on PATH. A path that doesn't exist is an error, never a download.
+pub fn set_uv_override(path: Option<PathBuf>) -> Result<()> {
+    let Some(path) = path.or_else(|| std::env::var_os("CASK_UV_PATH").filter(|v| !v.is_empty()).map(PathBuf::from)) else {
+        return Ok(());
+    };
+    let resolved = if path.components().count() == 1 && !path.is_absolute() {
+        find_on_path(&path).with_context(|| format!("uv override '{}' was not found on PATH", path.display()))?
+    } else {
+        // Absolute, since uv is often launched from inside an environment directory
+        std::path::absolute(&path)?
+    };
+    let _ = UV_OVERRIDE.set(resolved);
+    Ok(())
+}
+
+pub fn uv_override() -> Option<&'static Path> {
+    UV_OVERRIDE.get().map(PathBuf::as_path)
+}
+
+fn find_on_path(name: &Path) -> Option<PathBuf> {
+    let name = if cfg!(windows) && name.extension().is_none() { name.with_extension("exe") } else { name.to_path_buf() };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Checks that a user-provided uv exists, is executable and answers `--version`
+fn validate_override(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        anyhow::bail!("uv override {} does not exist (from --uv-path / CASK_UV_PATH)", path.display());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if fs::metadata(path)?.permissions().mode() & 0o111 == 0 {
+            anyhow::bail!("uv override {} is not executable", path.display());
+        }
+    }
+    let out = Command::new(path)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("Failed to run uv override {}", path.display()))?;
+    if !out.status.success() {
+        anyhow::bail!("uv override {} failed to run `--version`", path.display());
+    }
+    tracing::debug!("   Using {} ({})", path.display(), String::from_utf8_lossy(&out.stdout).trim());
+    Ok(())
+}
+
+/// What CASK_OFFLINE asks for ("1"/"true"/"yes" or "0"/"false"/"no"); None when unset.
+/// An explicit "0" beats `offline = true` in the global config.
+pub fn offline_from_env() -> Option<bool> {
+    let value = std::env::var("CASK_OFFLINE").ok()?;
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// The uv version used when a blueprint doesn't pin one: the last
+/// `cask upgrade` target, else the version this build of cask ships with
+pub fn default_version() -> String {
+    holotree::cask_home()
+        .ok()
+        .and_then(|home| fs::read_to_string(home.join("bin").join(DEFAULT_VERSION_FILE)).ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| validate_version(v).is_ok())
+        .unwrap_or_else(|| UV_VERSION.to_string())
+}
+
+/// Makes `version` the default engine. Written via rename so a crash never leaves a torn file.
+pub fn set_default_version(version: &str) -> Result<()> {
+    let bin_dir = holotree::cask_home()?.join("bin");
+    fs::create_dir_all(&bin_dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(&bin_dir)?;
+    writeln!(tmp, "{}", version)?;
+    tmp.persist(bin_dir.join(DEFAULT_VERSION_FILE))
+        .context("Failed to record the default engine version")?;
+    Ok(())
+}
+
+/// uv release names end up in paths and URLs, so keep them boring
+pub fn validate_version(version: &str) -> Result<()> {
+    let valid = !version.is_empty()
+        && version.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    if !valid {
+        anyhow::bail!("Invalid engine_version '{}' (expected a uv release like \"0.9.28\")", version);
+    }
+    Ok(())
+}
 
 pub struct Engine {
     pub path: PathBuf,
 }
 
 impl Engine {
-    pub fn ensure() -> Result<Self> {
-        let base_dirs = BaseDirs::new().context("Could not determine home directory")?;
-        let bin_dir = base_dirs.home_dir().join(".cask").join("bin");
-        
-        if !bin_dir.exists() {
-            fs::create_dir_all(&bin_dir)?;
+    /// Where the given uv version lives: $CASK_HOME/bin/uv-<version>/uv
+    pub fn locate(version: Option<&str>) -> Result<PathBuf> {
+        if let Some(path) = uv_override() {
+            return Ok(path.to_path_buf()); // Pins don't apply to a user-managed uv
         }
+        let version = version.map_or_else(default_version, str::to_string);
+        let bin_dir = holotree::cask_home()?.join("bin").join(format!("uv-{}", version));
 
         let uv_filename = if cfg!(windows) { "uv.exe" } else { "uv" };
-        let uv_path = bin_dir.join(uv_filename);
+        Ok(bin_dir.join(uv_filename))
+    }
+
+    /// Locates (or downloads) the requested uv version
+    pub fn ensure(version: Option<&str>) -> Result<Self> {
+        if let Some(path) = uv_override() {
+            validate_override(path)?;
+            return Ok(Self { path: path.to_path_buf() });
+        }
+        let uv_path = Self::locate(version)?;
+        let version = version.map_or_else(default_version, str::to_string);
+        let span = tracing::info_span!("bootstrap", uv_version = %version, downloaded = false).entered();
+        let bin_dir = uv_path.parent().context("Engine path has no parent")?.to_path_buf();
+
+        if !bin_dir.exists() {
+            fs::create_dir_all(&bin_dir)?;
+        }
 
         if uv_path.exists() {
             return Ok(Self { path: uv_path });
         }
 
-        println!("Engine missing. Bootstrapping CASK...");
-        download_and_unpack(UV_VERSION, &bin_dir)?;
+        if offline() {
+            anyhow::bail!(
+                "uv {} is not installed at {:?} and offline mode forbids downloading it",
+                version, uv_path
+            );
+        }
+        let _lock = holotree::lock_engine(&version)?;
+        if uv_path.exists() {
+            return Ok(Self { path: uv_path }); // Another cask finished the download while we waited
+        }
+        status!("Engine missing. Bootstrapping CASK (uv {})...", version);
+        span.record("downloaded", true);
+        download_and_unpack(&version, &bin_dir)?;
 
         if !uv_path.exists() {
             anyhow::bail!("Download completed but binary missing at {:?}", uv_path);
         }
 
-        println!("Engine ready.");
+        status!("Engine ready.");
         Ok(Self { path: uv_path })
     }
 }
 
+/// Where uv archives come from: the official releases, or a mirror from CASK_UV_BASE_URL
+#[derive(Clone)]
+struct ReleaseSource {
+    base_url: String,
+    /// Every request, including redirects, must stay on these hosts
+    hosts: Vec<String>,
+    /// CASK_UV_SHA256; replaces the published checksum (and is mandatory for mirrors)
+    sha256: Option<String>,
+}
+
+impl ReleaseSource {
+    fn from_env() -> Result<Self> {
+        let sha256 = match std::env::var("CASK_UV_SHA256").ok().filter(|v| !v.trim().is_empty()) {
+            Some(value) => {
+                let hash = value.trim().to_lowercase();
+                if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    anyhow::bail!("CASK_UV_SHA256 must be a sha256 hex digest, got '{}'", value);
+                }
+                Some(hash)
+            }
+            None => None,
+        };
+
+        let Some(mirror) = std::env::var("CASK_UV_BASE_URL").ok().filter(|v| !v.trim().is_empty()) else {
+            return Ok(Self {
+                base_url: BASE_URL.to_string(),
+                hosts: RELEASE_HOSTS.iter().map(|h| h.to_string()).collect(),
+                sha256,
+            });
+        };
+        let url = reqwest::Url::parse(mirror.trim())
+            .ok()
+            .filter(|u| secure_scheme(u) && u.has_host())
+            .with_context(|| format!("CASK_UV_BASE_URL must be an https:// URL, got '{}'", mirror))?;
+        if sha256.is_none() {
+            // A mirror's own .sha256 file proves nothing about the mirror
+            anyhow::bail!("CASK_UV_BASE_URL is set, so CASK_UV_SHA256 must name the expected archive digest");
+        }
+        Ok(Self {
+            base_url: url.as_str().trim_end_matches('/').to_string(),
+            hosts: vec![url.host_str().unwrap_or_default().to_string()],
+            sha256,
+        })
+    }
+
+    fn allows(&self, url: &reqwest::Url) -> bool {
+        secure_scheme(url) && url.host_str().is_some_and(|host| self.hosts.iter().any(|h| h == host))
+    }
+}
+
+/// Unit tests serve archives from a plain-http stub on localhost; everything else needs https
+fn secure_scheme(url: &reqwest::Url) -> bool {
+    url.scheme() == "https" || (cfg!(test) && url.scheme() == "http" && url.host_str() == Some("127.0.0.1"))
+}
+
 fn download_and_unpack(version: &str, target_dir: &Path) -> Result<()> {
     let (os, arch, ext) = detect_platform()?;
-    
+    let source = ReleaseSource::from_env()?;
+
     let asset_name = format!("uv-{}-{}.{}", arch, os, ext);
-    let url = format!("{}/{}/{}", BASE_URL, version, asset_name);
+    let url = format!("{}/{}/{}", source.base_url, version, asset_name);
+
+    tracing::debug!("   Downloading from: {}", url);
 
-    println!("   Downloading from: {}", url);
+    let client = build_client(&source)?;
+    let expected_hash = || match &source.sha256 {
+        Some(hash) => Ok(hash.clone()),
+        None => fetch_checksum(&client, &url),
+    };
+    let attempts = download_attempts()?;
+    let mut backoff = INITIAL_BACKOFF;
 
-    let client = reqwest::blocking::Client::new();
-    let mut response = client.get(&url).send()?;
+    // Extract beside the final location; the binary only moves into place once verified
+    let staging = tempfile::tempdir_in(target_dir)?;
+    let binary_name = if ext == "zip" { "uv.exe" } else { "uv" };
+
+    let mut attempt = 1;
+    loop {
+        let result = if ext == "zip" {
+            // Zip needs random access, so it still goes through a temp file
+            expected_hash().and_then(|hash| download_verified(&client, &url, &asset_name, &hash)).and_then(|archive| {
+                status!("   Unpacking...");
+                unpack_zip(archive, staging.path())
+            })
+        } else {
+            expected_hash().and_then(|hash| download_streaming_tar(&client, &url, &asset_name, &hash, staging.path()))
+        };
+        match result {
+            Ok(()) => break,
+            Err(e) if attempt < attempts => {
+                tracing::warn!("   Attempt {}/{} failed: {}", attempt, attempts, e);
+                tracing::warn!("   Retrying in {:?}...", backoff);
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) if is_timeout(&e) => {
+                let secs = net_timeout()?.as_secs();
+                return Err(e.context(format!(
+                    "Download stalled for {}s after {} attempt(s) (raise CASK_NET_TIMEOUT to allow slower links)",
+                    secs, attempts
+                )));
+            }
+            Err(e) => return Err(e.context(format!("Download failed after {} attempt(s)", attempts))),
+        }
+    }
+
+    let staged = staging.path().join(binary_name);
+    if !staged.exists() {
+        anyhow::bail!("{} does not contain a {} binary", asset_name, binary_name);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+    // Atomic, so anyone who finds the binary (with or without the engine lock) finds all of it
+    fs::rename(&staged, target_dir.join(binary_name))?;
+
+    Ok(())
+}
+
+fn unpack_zip(archive: File, dest: &Path) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(archive)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let outpath = match file.enclosed_name() {
+            Some(p) => p,
+            None => continue,
+        };
+
+        if let Some(fname) = outpath.file_name() {
+            let fname_str = fname.to_string_lossy();
+            if fname_str == "uv" || fname_str == "uv.exe" {
+                let mut outfile = File::create(dest.join(fname))?;
+                io::copy(&mut file, &mut outfile)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the .tar.gz straight off the response, so extraction overlaps the download.
+/// Every byte is hashed on the way through; `dest` is only trusted once the digest matches.
+fn download_streaming_tar(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    asset_name: &str,
+    expected_hash: &str,
+    dest: &Path,
+) -> Result<()> {
+    let response = client.get(url).send()?.error_for_status()?;
     let total_size = response.content_length().unwrap_or(0);
-    
+    let pb = download_bar(total_size)?;
+    let mut reader = HashingReader { inner: response, hasher: Sha256::new(), read: 0, pb: &pb };
+
+    if let Err(e) = extract_uv(&mut reader, dest) {
+        pb.abandon();
+        return Err(e);
+    }
+
+    if total_size > 0 && reader.read != total_size {
+        pb.abandon();
+        anyhow::bail!("Download truncated ({} of {} bytes)", reader.read, total_size);
+    }
+    pb.finish_with_message("Download complete");
+
+    verify_checksum(asset_name, expected_hash, reader.hasher)
+}
+
+/// Pulls the `uv` binary out of a .tar.gz stream, then drains the rest so it gets hashed too
+fn extract_uv(stream: &mut impl Read, dest: &Path) -> Result<()> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(stream));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        if path.file_name().is_some_and(|f| f == "uv") {
+            let mut outfile = File::create(dest.join("uv"))?;
+            io::copy(&mut entry, &mut outfile)?;
+        }
+    }
+    // The tar can end before the response body does; the checksum covers every byte
+    io::copy(&mut archive.into_inner().into_inner(), &mut io::sink())?;
+    Ok(())
+}
+
+/// Hashes and counts everything read through it, advancing the progress bar
+struct HashingReader<'a, R> {
+    inner: R,
+    hasher: Sha256,
+    read: u64,
+    pb: &'a DownloadProgress,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.read += n as u64;
+        self.pb.set_position(self.read);
+        Ok(n)
+    }
+}
+
+/// A bar on a terminal; otherwise (CI logs) a status line at every quarter of the download
+enum DownloadProgress {
+    Bar(ProgressBar),
+    Text { total: u64, reported: Cell<u64> },
+}
+
+/// Without a known size, a text line every this many bytes instead
+const TEXT_PROGRESS_STEP: u64 = 10 * 1024 * 1024;
+
+impl DownloadProgress {
+    fn set_position(&self, position: u64) {
+        match self {
+            DownloadProgress::Bar(pb) => pb.set_position(position),
+            DownloadProgress::Text { total, reported } => {
+                let step = if *total > 0 { total.div_ceil(4) } else { TEXT_PROGRESS_STEP };
+                if position / step > reported.get() / step && (*total == 0 || position < *total) {
+                    reported.set(position);
+                    match *total {
+                        0 => status!("   Downloaded {}", HumanBytes(position)),
+                        total => status!("   Downloaded {}% ({} of {})", position * 100 / total, HumanBytes(position), HumanBytes(total)),
+                    }
+                }
+            }
+        }
+    }
+
+    fn abandon(&self) {
+        if let DownloadProgress::Bar(pb) = self {
+            pb.abandon();
+        }
+    }
+
+    fn finish_with_message(&self, message: &'static str) {
+        match self {
+            DownloadProgress::Bar(pb) => pb.finish_with_message(message),
+            DownloadProgress::Text { .. } => status!("   {}", message),
+        }
+    }
+}
+
+fn download_bar(total_size: u64) -> Result<DownloadProgress> {
+    if ui::quiet() {
+        return Ok(DownloadProgress::Bar(ProgressBar::hidden()));
+    }
+    // indicatif draws on stderr and stays blank when that isn't a terminal
+    if !io::stderr().is_terminal() {
+        return Ok(DownloadProgress::Text { total: total_size, reported: Cell::new(0) });
+    }
     let pb = ProgressBar::new(total_size);
     pb.set_style(ProgressStyle::default_bar()
         .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
         .progress_chars("#>-"));
+    Ok(DownloadProgress::Bar(pb))
+}
+
+fn verify_checksum(asset_name: &str, expected_hash: &str, hasher: Sha256) -> Result<()> {
+    let actual_hash = hex::encode(hasher.finalize());
+    if actual_hash != expected_hash {
+        anyhow::bail!(
+            "Checksum mismatch for {}\n   expected: {}\n   actual:   {}",
+            asset_name, expected_hash, actual_hash
+        );
+    }
+    tracing::debug!("   Checksum verified (sha256:{})", &actual_hash[..16]);
+    Ok(())
+}
+
+/// Checks that the uv release host answers (used by `cask doctor`)
+pub fn check_reachability() -> Result<()> {
+    let source = ReleaseSource::from_env()?;
+    let client = build_client(&source)?;
+    client.head(&source.base_url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .with_context(|| format!("Could not reach {}", source.base_url))?;
+    Ok(())
+}
+
+/// Target triple of the uv build matching this host, e.g. "x86_64-unknown-linux-gnu"
+pub fn platform_triple() -> Result<String> {
+    let (os, arch, _) = detect_platform()?;
+    Ok(format!("{}-{}", arch, os))
+}
+
+/// Downloads one copy of the archive into a fresh temp file and checks its digest.
+/// Any failure drops the temp file, so a truncated archive is never unpacked.
+fn download_verified(client: &reqwest::blocking::Client, url: &str, asset_name: &str, expected_hash: &str) -> Result<File> {
+    let mut response = client.get(url).send()?.error_for_status()?;
+    let total_size = response.content_length().unwrap_or(0);
+    let pb = download_bar(total_size)?;
 
     let mut temp_archive = tempfile::tempfile()?;
+    let mut hasher = Sha256::new();
     let mut downloaded: u64 = 0;
     let mut buf = [0; 8192];
     
     // This loop requires `use std::io::Read;`
     loop {
-        let n = response.read(&mut buf)?;
+        let n = match response.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) => {
+                pb.abandon();
+                return Err(e.into());
+            }
+        };
         if n == 0 { break; }
         temp_archive.write_all(&buf[..n])?;
+        hasher.update(&buf[..n]);
         downloaded += n as u64;
         pb.set_position(downloaded);
     }
+
+    if total_size > 0 && downloaded != total_size {
+        pb.abandon();
+        anyhow::bail!("Download truncated ({} of {} bytes)", downloaded, total_size);
+    }
     pb.finish_with_message("Download complete");
 
-    use std::io::Seek;
+    verify_checksum(asset_name, expected_hash, hasher)?;
     temp_archive.seek(io::SeekFrom::Start(0))?;
+    Ok(temp_archive)
+}
 
-    println!("   Unpacking...");
-
-    if ext == "zip" {
-        let mut archive = zip::ZipArchive::new(temp_archive)?;
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let outpath = match file.enclosed_name() {
-                Some(p) => p,
-                None => continue,
-            };
-
-            if let Some(fname) = outpath.file_name() {
-                let fname_str = fname.to_string_lossy();
-                if fname_str == "uv" || fname_str == "uv.exe" {
-                    let mut outfile = File::create(target_dir.join(fname))?;
-                    io::copy(&mut file, &mut outfile)?;
-                }
-            }
-        }
-    } else {
-        let tar = flate2::read::GzDecoder::new(temp_archive);
-        let mut archive = tar::Archive::new(tar);
-        for entry in archive.entries()? {
-            let mut entry = entry?;
-            let path = entry.path()?;
-            if let Some(fname) = path.file_name() {
-                if fname == "uv" {
-                    let mut outfile = File::create(target_dir.join("uv"))?;
-                    io::copy(&mut entry, &mut outfile)?;
-                }
-            }
+/// Number of download attempts, overridable via CASK_DOWNLOAD_RETRIES. Also bounds the
+/// attempts at a package install.
+pub fn download_attempts() -> Result<u32> {
+    match std::env::var("CASK_DOWNLOAD_RETRIES") {
+        Ok(value) => {
+            let attempts: u32 = value.trim().parse()
+                .with_context(|| format!("CASK_DOWNLOAD_RETRIES must be a positive integer, got '{}'", value))?;
+            Ok(attempts.max(1))
         }
+        Err(_) => Ok(DEFAULT_DOWNLOAD_ATTEMPTS),
     }
+}
 
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let uv_bin = target_dir.join("uv");
-        if uv_bin.exists() {
-            fs::set_permissions(&uv_bin, fs::Permissions::from_mode(0o755))?;
+/// Network timeout in seconds, overridable via CASK_NET_TIMEOUT
+fn net_timeout() -> Result<Duration> {
+    match std::env::var("CASK_NET_TIMEOUT") {
+        Ok(value) => {
+            let secs: u64 = value.trim().parse().ok().filter(|secs| *secs > 0)
+                .with_context(|| format!("CASK_NET_TIMEOUT must be a positive number of seconds, got '{}'", value))?;
+            Ok(Duration::from_secs(secs))
         }
+        Err(_) => Ok(DEFAULT_NET_TIMEOUT),
     }
+}
 
-    Ok(())
+/// Whether a download failed because the connection or a read timed out
+fn is_timeout(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.downcast_ref::<reqwest::Error>().is_some_and(reqwest::Error::is_timeout)
+            || cause.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::TimedOut)
+    })
+}
+
+/// Builds the HTTP client, honoring HTTPS_PROXY / HTTP_PROXY / NO_PROXY. Redirects that
+/// leave `source`'s hosts (or https) are refused; rustls still validates the certificate.
+fn build_client(source: &ReleaseSource) -> Result<reqwest::blocking::Client> {
+    Ok(client_builder()?.https_only(!cfg!(test)).redirect(pinned_redirects(source)).build()?)
+}
+
+/// An https-only client for other downloads (e.g. `cask init --template <url>`), with the
+/// same proxy and timeout handling as engine downloads but no host pinning
+pub fn http_client() -> Result<reqwest::blocking::Client> {
+    Ok(client_builder()?.https_only(true).build()?)
+}
+
+fn client_builder() -> Result<reqwest::blocking::ClientBuilder> {
+    // The blocking client applies `timeout` to connecting and to each read, not the whole
+    // transfer, so a slow but steady download is fine while a stalled one gives up
+    let timeout = net_timeout()?;
+    // Drop reqwest's implicit env detection so a malformed proxy fails loudly here
+    let mut builder = reqwest::blocking::Client::builder()
+        .no_proxy()
+        .connect_timeout(timeout)
+        .timeout(timeout);
+
+    for var in ["HTTPS_PROXY", "HTTP_PROXY"] {
+        let Some(value) = proxy_var(var) else { continue };
+
+        // Don't echo the value back: proxy URLs often carry credentials
+        let url = reqwest::Url::parse(&value)
+            .ok()
+            .filter(|u| u.has_host() && matches!(u.scheme(), "http" | "https"))
+            .with_context(|| format!("{} is not a valid proxy URL (expected e.g. http://proxy.corp:8080)", var))?;
+
+        tracing::debug!("   Using proxy from {}", var);
+        let proxy = if var == "HTTPS_PROXY" {
+            reqwest::Proxy::https(url)?
+        } else {
+            reqwest::Proxy::http(url)?
+        };
+        builder = builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_env()));
+    }
+
+    Ok(builder)
+}
+
+fn pinned_redirects(source: &ReleaseSource) -> reqwest::redirect::Policy {
+    let source = source.clone();
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 10 {
+            attempt.error("too many redirects")
+        } else if source.allows(attempt.url()) {
+            attempt.follow()
+        } else {
+            let message = format!(
+                "refusing redirect to unexpected host {} (allowed: {})",
+                attempt.url().host_str().unwrap_or("?"), source.hosts.join(", ")
+            );
+            attempt.error(message)
+        }
+    })
+}
+
+/// Proxy variables are conventionally accepted in either case
+fn proxy_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_lowercase()))
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+}
+
+/// Fetches the `.sha256` companion published next to every uv release asset
+fn fetch_checksum(client: &reqwest::blocking::Client, asset_url: &str) -> Result<String> {
+    let checksum_url = format!("{}.sha256", asset_url);
+    let body = client.get(&checksum_url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.text())
+        .with_context(|| format!("Failed to fetch checksum from {}", checksum_url))?;
+
+    // Format is "<hex digest>  <filename>"
+    let hash = body.split_whitespace().next().unwrap_or_default().to_lowercase();
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Malformed checksum file at {}", checksum_url);
+    }
+    Ok(hash)
 }
 
 fn detect_platform() -> Result<(&'static str, &'static str, &'static str)> {
@@ -133,4 +652,71 @@ fn detect_platform() -> Result<(&'static str, &'static str, &'static str)> {
     let ext = if cfg!(target_os = "windows") { "zip" } else { "tar.gz" };
 
     Ok((os, arch, ext))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Sandbox;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    const UV_SCRIPT: &[u8] = b"#!/bin/sh\necho uv 0.0.1-test\n";
+
+    /// A release archive like uv's: the binary inside a top-level directory
+    fn release_archive() -> Vec<u8> {
+        let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(UV_SCRIPT.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        archive.append_data(&mut header, "uv-stub/uv", UV_SCRIPT).unwrap();
+        archive.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Serves `body` for every request on a local port, slowly enough that concurrent
+    /// bootstraps overlap; returns the base URL and the request counter
+    fn serve(body: Vec<u8>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(300));
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&body));
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn concurrent_bootstraps_download_once() {
+        let mut sandbox = Sandbox::new();
+        let archive = release_archive();
+        sandbox.set_env("CASK_UV_SHA256", &hex::encode(Sha256::digest(&archive)));
+        let (url, requests) = serve(archive);
+        sandbox.set_env("CASK_UV_BASE_URL", &url);
+
+        let version = "0.0.1-test";
+        let engines: Vec<_> = (0..2).map(|_| thread::spawn(move || Engine::ensure(Some(version)))).collect();
+        let paths: Vec<PathBuf> = engines.into_iter().map(|engine| engine.join().unwrap().unwrap().path).collect();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(paths[0], paths[1]);
+        assert_eq!(fs::read(&paths[0]).unwrap(), UV_SCRIPT);
+        let bin_dir = holotree::cask_home().unwrap().join("bin").join(format!("uv-{}", version));
+        let entries: Vec<_> = fs::read_dir(bin_dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(entries, ["uv"], "only the binary, no leftover staging");
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{holotree, secrets, ui};
+
+/// Every log `cask run --capture` writes ends in this, which is also how `--keep` finds them
+const LOG_SUFFIX: &str = "_run.log";
+
+/// One run's log file: a header with the command, the payload's output, a footer with the exit code
+pub struct RunLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    started: Instant,
+}
+
+impl RunLog {
+    /// Creates `<dir>/<UTC timestamp>_run.log` and, with `keep`, deletes all but the newest `keep` logs
+    pub fn create(dir: &Path, keep: Option<usize>, command: &Command) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Cannot create log directory {:?}", dir))?;
+        let now = holotree::format_utc(holotree::unix_now());
+        let stamp = now.trim_end_matches('Z').replace('T', "_").replace(':', "-");
+
+        // Parallel runs can start within the same second
+        let (path, mut file) = (0..)
+            .map(|n| match n {
+                0 => dir.join(format!("{}{}", stamp, LOG_SUFFIX)),
+                n => dir.join(format!("{}-{}{}", stamp, n, LOG_SUFFIX)),
+            })
+            .find_map(|path| match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => Some(Ok((path, file))),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                Err(e) => Some(Err(e)),
+            })
+            .expect("unbounded range")
+            .with_context(|| format!("Cannot create a log file in {:?}", dir))?;
+        // Held until the run ends, so a sibling run's rotation leaves this log alone
+        file.try_lock_exclusive().with_context(|| format!("Cannot lock {:?}", path))?;
+
+        writeln!(file, "# cask run: {}", secrets::redact(&ui::command_line(command)))?;
+        if let Some(cwd) = command.get_current_dir() {
+            writeln!(file, "# cwd: {}", cwd.display())?;
+        }
+        writeln!(file, "# started: {}", now)?;
+
+        if let Some(keep) = keep {
+            rotate(dir, keep.max(1))?;
+        }
+        Ok(Self { path, file: Mutex::new(file), started: Instant::now() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends one line of payload output; env-file secrets never reach the disk
+    pub fn line(&self, text: &str) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(file, "{}", secrets::redact(text));
+    }
+
+    pub fn finish(&self, status: &ExitStatus) -> Result<()> {
+        let code = status.code().map_or_else(|| "none (terminated by a signal)".to_string(), |c| c.to_string());
+        self.footer(&format!("exit code: {}", code))
+    }
+
+    /// Footer for a run that ended in an error instead of an exit status (e.g. a timeout)
+    pub fn abort(&self, error: &anyhow::Error) -> Result<()> {
+        self.footer(&format!("aborted: {}", error))
+    }
+
+    fn footer(&self, outcome: &str) -> Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(file, "# {} after {:.1}s", outcome, self.started.elapsed().as_secs_f64())?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Deletes the oldest logs by creation time (names alone can't order runs within one second).
+/// Logs of runs still in progress are locked and skipped.
+fn rotate(dir: &Path, keep: usize) -> Result<()> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.file_name().is_some_and(|n| n.to_string_lossy().ends_with(LOG_SUFFIX)))
+        .collect();
+    logs.sort_by_key(|path| {
+        let created = fs::metadata(path).and_then(|m| m.created().or_else(|_| m.modified())).ok();
+        (created, path.clone())
+    });
+    let mut excess = logs.len().saturating_sub(keep);
+    for old in &logs {
+        if excess == 0 {
+            break;
+        }
+        let idle = File::open(old).is_ok_and(|file| file.try_lock_exclusive().is_ok());
+        if idle {
+            fs::remove_file(old).with_context(|| format!("Cannot remove old log {:?}", old))?;
+            excess -= 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(dir: &Path) -> std::collections::BTreeSet<PathBuf> {
+        fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect()
+    }
+
+    #[test]
+    fn rotation_spares_logs_of_running_siblings() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = Command::new("true");
+        let finished = RunLog::create(dir.path(), None, &command).unwrap();
+        let finished_path = finished.path().to_path_buf();
+        drop(finished);
+        let running = RunLog::create(dir.path(), None, &command).unwrap();
+
+        let current = RunLog::create(dir.path(), Some(1), &command).unwrap();
+        assert_eq!(logs(dir.path()), [running.path().to_path_buf(), current.path().to_path_buf()].into());
+        assert!(!finished_path.exists());
+    }
+}
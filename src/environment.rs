@@ -0,0 +1,1284 @@
+//! Resolving, locking, building and running environments, independent of the CLI.
+//! Progress is reported through `tracing` (see `status!`), so embedders decide what is shown.
+
+use anyhow::{Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+
+use crate::capture::RunLog;
+use crate::exit::{self, Classify, Failure};
+use crate::hooks::{self, Event};
+use crate::interrupt::BuildDeadline;
+use crate::{bail_as, bootstrap, config, holotree, identity, interrupt, jobs, lockfile, secrets, status, ui};
+
+/// Like `--frozen-lockfile`, which it is read into when "1", "true" or "yes"
+pub const FROZEN_LOCKFILE_VAR: &str = "CASK_FROZEN_LOCKFILE";
+
+/// Set once from `--frozen-lockfile` / CASK_FROZEN_LOCKFILE: every environment must come
+/// from an existing lock that fits, and nothing ever relocks. Independent of offline mode,
+/// which only forbids the network; together they make a build fully hermetic.
+static FROZEN_LOCKFILE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_frozen_lockfile(frozen: bool) {
+    FROZEN_LOCKFILE.store(frozen, Ordering::Relaxed);
+}
+
+pub fn frozen_lockfile() -> bool {
+    FROZEN_LOCKFILE.load(Ordering::Relaxed)
+}
+
+pub fn frozen_lockfile_from_env() -> bool {
+    std::env::var(FROZEN_LOCKFILE_VAR).is_ok_and(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+/// First pause before retrying a failed install; doubles with each further attempt
+const INSTALL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Knobs that change how an environment is resolved
+#[derive(Default, Clone)]
+pub struct EnvOptions {
+    /// Overrides the blueprint's python (never written back to disk)
+    pub python: Option<String>,
+    /// Include dev_dependencies (and use cask.dev.lock)
+    pub dev: bool,
+    /// Selected optional_dependencies groups, sorted and deduplicated
+    pub extras: Vec<String>,
+    /// Treat drift as an error instead of relocking
+    pub frozen: bool,
+    /// Never build, relock or install an interpreter; error out instead
+    pub no_build: bool,
+    /// Like `no_build`, but only report what would happen instead of failing
+    pub dry_run: bool,
+    /// Never relock; a lock that doesn't fit this run is bypassed for the config's loose deps
+    pub keep_lock: bool,
+    /// Lockfile to use instead of the one `lock_path_for` derives next to the config
+    pub lock: Option<PathBuf>,
+}
+
+/// What `cask run` executes
+pub enum Payload {
+    /// Arguments for the environment's python
+    Python(Vec<String>),
+    /// A blueprint task; `argv` already includes any extra arguments
+    Task { name: String, argv: Vec<String> },
+}
+
+impl Payload {
+    /// A first argument naming a task selects it; everything after (minus a
+    /// leading `--`) is appended, so `cask run test -- -k slow` works
+    pub fn resolve(blueprint: &config::Blueprint, args: &[String]) -> Result<Self> {
+        let Some((first, rest)) = args.split_first() else {
+            return Ok(Payload::Python(Vec::new()));
+        };
+        let Some(mut argv) = blueprint.task_command(first)? else {
+            return Ok(Payload::Python(args.to_vec()));
+        };
+        let rest = rest.strip_prefix(&["--".to_string()]).unwrap_or(rest);
+        argv.extend(rest.iter().cloned());
+        Ok(Payload::Task { name: first.clone(), argv })
+    }
+}
+
+/// A resolved (and built) Holotree environment, ready to execute in
+pub struct PreparedEnv {
+    pub blueprint: config::Blueprint,
+    pub env_hash: String,
+    pub env_path: PathBuf,
+    pub project_root: PathBuf,
+    pub cached: bool,
+}
+
+/// Resolves config & lockfile, computes the identity and builds the env if needed
+pub fn prepare_env(uv: &Path, config: &Path, opts: &EnvOptions) -> Result<PreparedEnv> {
+    let span = tracing::info_span!("prepare", config = %config.display(), hash = Empty, python = Empty, cache = Empty).entered();
+
+    // A. Resolve Project Root (for .env and relative paths)
+    let project_root = config.parent()
+        .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p })
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+
+    // B. Check for Lockfile & Auto-Update if Stale (Drift Detection)
+    let lock_path = lock_path_for(config, opts);
+    let mut use_lock = lock_path.exists();
+    if !use_lock && frozen_lockfile() {
+        bail_as!(Failure::Build,
+            "--frozen-lockfile requires {}, but it does not exist. Run `cask lock` and commit the result.",
+            lock_path.display()
+        );
+    }
+
+    if let Some(reason) = lock_drift(config, &lock_path, opts).classify(Failure::Build)? {
+        if frozen_lockfile() {
+            bail_as!(Failure::Build,
+                "{} is out of date ({}), and --frozen-lockfile forbids relocking. Run `cask lock` and commit the result.",
+                lock_path.display(), reason
+            );
+        } else if opts.dry_run {
+            status!("{} Would relock {} ({}); using the current lock for now", "🔄".yellow(), lock_path.display(), reason);
+        } else if opts.keep_lock {
+            tracing::warn!("{} {} does not fit this run ({}); using loose dependencies.", "⚠️".yellow(), lock_path.display(), reason);
+            use_lock = false;
+        } else if bootstrap::offline() {
+            bail_as!(Failure::Build,
+                "Dependency drift detected in {} ({}), but relocking needs the network. Run `cask lock` while online.",
+                lock_path.display(), reason
+            );
+        } else if opts.frozen || opts.no_build {
+            bail_as!(Failure::Build,
+                "Dependency drift detected in {} ({}). Run `cask lock` and commit the result.",
+                lock_path.display(), reason
+            );
+        } else {
+            tracing::warn!(lockfile = %lock_path.display(), "{} Dependency drift detected ({}).", "🔄".yellow(), reason);
+            let _relock = holotree::lock_lockfile(&lock_path)?;
+            // Parallel runs of a drifted project relock once; the rest reuse the result
+            if lock_drift(config, &lock_path, opts).classify(Failure::Build)?.is_none() {
+                status!("{} {} was relocked by another cask process.", "⚡".green(), lock_path.display());
+            } else {
+                write_lock(uv, config, opts, &Upgrade::Nothing).classify(Failure::Build)?;
+            }
+        }
+    }
+
+    // C. Determine Effective Configuration (Lock vs YAML)
+    let (_, effective_config) = if use_lock {
+        status!("{} Found {}. Enforcing Strict Mode.", "🛡️".green(), lock_path.display());
+        (true, lock_path.as_path())
+    } else {
+        if !lock_path.exists() {
+            tracing::warn!("{} No lockfile found. Using loose dependencies.", "⚠️".yellow());
+        }
+        (false, config)
+    };
+
+    // D. Load Blueprint (We always need this for Metadata & Python Version)
+    if !config.exists() {
+        bail_as!(Failure::Config, "Config file not found: {:?}", config);
+    }
+    let mut blueprint = config::Blueprint::load(config)?;
+    if let Some(version) = &opts.python {
+        // Only the in-memory copy changes; the hash below picks it up
+        blueprint.python = version.clone();
+    }
+    blueprint.validate_extras(&opts.extras).classify(Failure::Config)?;
+    blueprint.check_dependencies(config, opts.dev, &opts.extras, &lock_path).classify(Failure::Config)?;
+
+    // A range must be pinned to a concrete interpreter before hashing,
+    // otherwise two machines could share a hash but run different Pythons
+    let python_spec = if blueprint.python_is_range() {
+        let spec = blueprint.python.clone();
+        blueprint.python = resolve_python(uv, &blueprint, !opts.no_build && !opts.dry_run && !bootstrap::offline())
+            .classify(Failure::Build)?;
+        status!("{} Resolved Python {} -> {}", "🐍".magenta(), spec, blueprint.python);
+        Some(spec)
+    } else {
+        None
+    };
+
+    if let Some(name) = &blueprint.name {
+        status!("🤖 Project: {}", name.cyan().bold());
+    }
+    if let Some(desc) = &blueprint.description {
+        status!("📄 {}", desc.italic());
+    }
+
+    // E. Resolve Holotree Path
+    let holotree_root = holotree::root()?;
+    
+    // F. Calculate Identity (Content-Addressable Hash)
+    let env_hash = identity::calculate_hash(effective_config, &blueprint, opts)?;
+    let env_path = holotree_root.join(&env_hash);
+
+    status!(env_hash = %env_hash, python = %blueprint.python, "{} Identity: {} (Python {})", "🆔".blue(), env_hash, blueprint.python);
+    tracing::debug!("   Holotree path: {}", env_path.display());
+    span.record("hash", env_hash.as_str()).record("python", blueprint.python.as_str());
+
+    // G. Build (if missing, with Self-Healing)
+    let usable = |path: &Path| holotree::is_complete(path) && holotree::python_launches(path);
+    let mut cached = usable(&env_path);
+    if !cached && opts.no_build {
+        bail_as!(Failure::Build, "Environment {} is not built yet (drop --no-build to build it)", env_hash);
+    }
+    if !cached && !opts.dry_run && holotree::readonly() {
+        bail_as!(
+            Failure::Build,
+            "Environment {} is not provisioned. {} is read-only ({}=1), so it must be built by a provisioning run without that flag.",
+            env_hash, holotree_root.display(), holotree::READONLY_VAR
+        );
+    }
+    // Parallel runs of the same project must not build the same env twice
+    let build_lock = if !cached && !opts.dry_run { Some(holotree::lock_build(&env_hash)?) } else { None };
+    if build_lock.is_some() && usable(&env_path) {
+        status!("{} Environment was built by another cask process.", "⚡".green());
+        cached = true;
+    } else if !cached && !opts.dry_run {
+        if env_path.exists() {
+            // Only a damaged env gets here now: its interpreter was uninstalled, or it was
+            // built for another platform sharing this $CASK_HOME
+            tracing::warn!("{} Cached environment is unusable (incomplete, or its Python won't start). Rebuilding...", "🩹".yellow());
+            fs::remove_dir_all(&env_path)?;
+        }
+        status!("{} Building Holotree node...", "🔨".yellow());
+        let _slot = jobs::build_slot();
+        let _span = tracing::info_span!("build", hash = %env_hash, python = %blueprint.python, locked = use_lock).entered();
+        let _defer = interrupt::defer(); // Ctrl-C must not leave a half-built env behind
+        let deadline = interrupt::build_deadline().classify(Failure::Config)?;
+
+        // Built off to the side and renamed into place, so env_path is never half-built
+        let staging = holotree::staging_path(&env_hash)?;
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        let metadata = || {
+            holotree::Metadata::new(blueprint.name.clone(), &blueprint.python, python_spec.clone(), config, blueprint.venv_name())
+        };
+        // Written before building too, so every step already finds the venv under its name
+        let built = fs::create_dir_all(&staging)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| metadata().write(&staging))
+            .and_then(|_| build_env(uv, &staging, effective_config, &blueprint, opts, &deadline))
+            .and_then(|_| venv_interpreter(uv, &staging, &env_path, &deadline))
+            .and_then(|interpreter| metadata().with_interpreter(interpreter).write(&staging))
+            .and_then(|_| write_provenance(uv, &staging, &env_hash, effective_config, &blueprint))
+            .and_then(|_| holotree::mark_complete(&staging))
+            .and_then(|_| fs::rename(&staging, &env_path).context("Failed to move the finished build into place"));
+        if let Err(e) = built {
+            if interrupt::interrupted() {
+                tracing::warn!("{} Build interrupted. Removing partial environment...", "🛑".yellow());
+            } else {
+                tracing::error!("{} Build failed. Cleaning up...", "💥".red());
+            }
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e).classify(Failure::Build);
+        }
+        // Only a convenience; a failure here must not throw away a good build
+        match holotree::link_alias(&env_path, &env_hash) {
+            Ok(Some(alias)) => status!("{} Alias: {}/{}", "🔗".cyan(), holotree::ALIAS_DIR, alias),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("{} Could not create a by-name alias: {:#}", "⚠️".yellow(), e),
+        }
+    } else if cached {
+        status!("{} Using cached environment.", "⚡".green());
+    }
+
+    span.record("cache", if cached { "hit" } else { "miss" });
+    let built = !cached && !opts.dry_run;
+    let prepared = PreparedEnv { blueprint, env_hash, env_path, project_root, cached };
+    if built {
+        hooks::fire(Event::Build, &prepared).classify(Failure::Build)?;
+    }
+    Ok(prepared)
+}
+
+/// Each dependency selection gets its own lockfile so cask.lock stays production-only:
+/// cask.lock, cask.dev.lock, cask.ocr+pdf.lock, cask.dev.ocr+pdf.lock. An explicit
+/// `opts.lock` (`--lock`/`--output`) wins, e.g. for repos that keep locks in `locks/`.
+pub fn lock_path_for(config_path: &Path, opts: &EnvOptions) -> PathBuf {
+    if let Some(lock) = &opts.lock {
+        return lock.clone();
+    }
+    let mut name = String::from("cask");
+    if opts.dev {
+        name.push_str(".dev");
+    }
+    if !opts.extras.is_empty() {
+        name.push('.');
+        name.push_str(&opts.extras.join("+"));
+    }
+    config_path.with_file_name(format!("{}.lock", name))
+}
+
+/// Order and repetition of --extras must not change the environment identity
+pub fn normalize_extras(extras: &[String]) -> Vec<String> {
+    let mut extras: Vec<String> = extras
+        .iter()
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .collect();
+    extras.sort();
+    extras.dedup();
+    extras
+}
+
+/// Why the lockfile can't be used as-is: cask.yaml changed since, or the lock
+/// was compiled for another Python or OS/arch. None if it's fine (or there is none).
+pub fn lock_drift(config_path: &Path, lock_path: &Path, opts: &EnvOptions) -> Result<Option<String>> {
+    if !config_path.exists() || !lock_path.exists() {
+        return Ok(None);
+    }
+    let blueprint = config::Blueprint::load(config_path)?;
+    if lock_is_stale(config_path, &blueprint, lock_path, opts)? {
+        return Ok(Some("cask.yaml changed".to_string()));
+    }
+    // Pre-header locks are judged by their contents; `cask lock` adds the header
+    let header = lockfile::LockHeader::read_or_infer(lock_path)?;
+    let python = opts.python.as_deref().unwrap_or(&blueprint.python);
+    Ok(header.mismatch(python))
+}
+
+/// The lock is stale once what it was compiled from changed (per the `inputs` its header
+/// records); for older locks, once the config or any config it extends was edited after it
+pub fn lock_is_stale(config_path: &Path, blueprint: &config::Blueprint, lock_path: &Path, opts: &EnvOptions) -> Result<bool> {
+    if let Some(locked) = lockfile::LockHeader::read(lock_path)?.and_then(|header| header.inputs) {
+        return Ok(locked != identity::lock_inputs(blueprint, opts)?);
+    }
+    // Locks from before input hashes can only be judged by modification time
+    let locked_at = fs::metadata(lock_path)?.modified()?;
+    for source in std::iter::once(config_path).chain(blueprint.inherited_from.iter().map(PathBuf::as_path)) {
+        if fs::metadata(source)?.modified()? > locked_at {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Which pins `cask lock` may move away from the existing lock
+pub enum Upgrade {
+    Nothing,
+    All,
+    Packages(Vec<String>),
+}
+
+impl Upgrade {
+    /// `--upgrade` values; a bare flag arrives as an empty string
+    pub fn from_flags(values: &[String]) -> Self {
+        if values.is_empty() {
+            Upgrade::Nothing
+        } else if values.iter().any(String::is_empty) {
+            Upgrade::All
+        } else {
+            Upgrade::Packages(values.to_vec())
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            Upgrade::Nothing => Vec::new(),
+            Upgrade::All => vec!["--upgrade".to_string()],
+            Upgrade::Packages(names) => names.iter().flat_map(|n| ["--upgrade-package".to_string(), n.clone()]).collect(),
+        }
+    }
+}
+
+pub fn lock_dependencies(uv: &Path, config_path: &Path, opts: &EnvOptions, upgrade: &Upgrade) -> Result<()> {
+    if frozen_lockfile() {
+        anyhow::bail!("--frozen-lockfile forbids writing the lockfile (`cask lock --check` still works)");
+    }
+    let _relock = holotree::lock_lockfile(&lock_path_for(config_path, opts))?;
+    write_lock(uv, config_path, opts, upgrade)
+}
+
+/// Resolves the blueprint into its lockfile; the caller holds `holotree::lock_lockfile`
+fn write_lock(uv: &Path, config_path: &Path, opts: &EnvOptions, upgrade: &Upgrade) -> Result<()> {
+    let _span = tracing::info_span!("lock", config = %config_path.display()).entered();
+    status!("{} Locking dependencies...", "🔒".cyan());
+    match upgrade {
+        Upgrade::Nothing => {}
+        Upgrade::All => status!("{} Upgrading all packages", "⬆️".cyan()),
+        Upgrade::Packages(names) => status!("{} Upgrading {}", "⬆️".cyan(), names.join(", ")),
+    }
+
+    let lock_file = lock_path_for(config_path, opts);
+    config::Blueprint::load(config_path)?.check_dependencies(config_path, opts.dev, &opts.extras, &lock_file)?;
+    if let Some(dir) = lock_file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let previous = fs::read(&lock_file).ok();
+    compile_lock(uv, config_path, opts, &lock_file, upgrade)?;
+
+    let content = fs::read_to_string(&lock_file)?;
+    if let Err(e) = config::GlobalConfig::get()?.check_packages(content.lines(), &lock_file.display().to_string()) {
+        // A lock that breaks policy must not be left behind for the next build to use
+        match previous {
+            Some(previous) => fs::write(&lock_file, previous)?,
+            None => fs::remove_file(&lock_file)?,
+        }
+        return Err(e);
+    }
+
+    status!("{} Locked to {:?}", "✅".green(), lock_file);
+    Ok(())
+}
+
+/// Resolves into a scratch file and compares it with the committed lock
+pub fn check_lock(uv: &Path, config_path: &Path, opts: &EnvOptions) -> Result<()> {
+    status!("{} Checking lockfile...", "🔒".cyan());
+
+    let lock_file = lock_path_for(config_path, opts);
+    if !lock_file.exists() {
+        anyhow::bail!("{} does not exist. Run `cask lock` to create it.", lock_file.display());
+    }
+
+    // Seeded with the current pins, like a real relock, so only genuine drift shows up
+    let scratch = tempfile::NamedTempFile::new()?;
+    fs::copy(&lock_file, scratch.path())?;
+    compile_lock(uv, config_path, opts, scratch.path(), &Upgrade::Nothing)?;
+
+    // Header comments embed the output path, so compare only pins and annotations
+    let pins = |content: String| -> Vec<String> {
+        content.lines().filter(|l| !l.starts_with('#')).map(str::to_string).collect()
+    };
+    let resolved = fs::read_to_string(scratch.path())?;
+    config::GlobalConfig::get()?.check_packages(resolved.lines(), "The resolved dependency set")?;
+    let fresh = pins(resolved);
+    let current = pins(fs::read_to_string(&lock_file)?);
+
+    if fresh != current {
+        anyhow::bail!("{} is out of date. Run `cask lock` to update it.", lock_file.display());
+    }
+
+    status!("{} {} is up to date", "✅".green(), lock_file.display());
+    Ok(())
+}
+
+/// Runs `uv pip compile` for the blueprint, writing the result to `output`. uv reads an
+/// existing `output` as preferred pins, so a relock only moves what the config change
+/// (or `upgrade`) requires and the lock diff stays minimal.
+fn compile_lock(uv: &Path, config_path: &Path, opts: &EnvOptions, output: &Path, upgrade: &Upgrade) -> Result<()> {
+    let mut blueprint = config::Blueprint::load(config_path)?;
+    if let Some(version) = &opts.python {
+        blueprint.python = version.clone();
+    }
+    // Beside the config so relative `-r` includes resolve; unique so concurrent compiles don't collide
+    let dir = config_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let temp_reqs = tempfile::Builder::new().prefix(".cask-reqs-").suffix(".txt").tempfile_in(dir)?;
+    fs::write(temp_reqs.path(), blueprint.to_requirements_txt(opts.dev, &opts.extras)?)?;
+
+    print_indexes(&blueprint);
+
+    let mut compile = Command::new(uv);
+    compile.arg("pip")
+        .arg("compile")
+        .arg(temp_reqs.path())
+        .arg("-o")
+        .arg(output)
+        .arg("--python")
+        .arg(&blueprint.python)
+        .arg("--generate-hashes")
+        .args(upgrade.args())
+        .args(blueprint.constraint_args())
+        .args(offline_args())
+        .args(blueprint.index_args())
+        .args(&blueprint.uv_args)
+        .stdout(ui::child_stdout());
+    if ui::quiet() {
+        compile.arg("--quiet"); // uv still reports resolution errors
+    }
+    ui::trace_command(&compile);
+    let status = interrupt::status(&mut compile)?;
+
+    if !status.success() {
+        anyhow::bail!("Failed to lock dependencies");
+    }
+    // `# via -r` annotations name the input; keep them stable rather than naming the random file
+    if let (Some(temp_name), Some(config_name)) = (temp_reqs.path().file_name(), config_path.file_name()) {
+        let content = fs::read_to_string(output)?;
+        fs::write(output, content.replace(&*temp_name.to_string_lossy(), &config_name.to_string_lossy()))?;
+    }
+    lockfile::write_header(output, &blueprint.python, Some(identity::lock_inputs(&blueprint, opts)?))
+}
+
+/// `--offline` for uv commands that may otherwise download packages or interpreters
+pub fn offline_args() -> &'static [&'static str] {
+    if bootstrap::offline() { &["--offline"] } else { &[] }
+}
+
+/// `uv --version` output, e.g. "uv 0.9.28"
+pub fn engine_version(uv: &Path) -> Result<String> {
+    let out = Command::new(uv).arg("--version").output()?;
+    if !out.status.success() {
+        anyhow::bail!("{} --version failed", uv.display());
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// Records how a freshly built environment came to be (provenance.json)
+fn write_provenance(uv: &Path, env_path: &Path, env_hash: &str, source: &Path, blueprint: &config::Blueprint) -> Result<()> {
+    let content = fs::read(source).with_context(|| format!("Failed to read {:?}", source))?;
+    holotree::Provenance {
+        env_hash: env_hash.to_string(),
+        config_sha256: hex::encode(Sha256::digest(&content)),
+        uv_version: engine_version(uv)?,
+        python: holotree::venv_python_version(env_path).unwrap_or_else(|| blueprint.python.clone()),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        host: holotree::hostname(),
+        built_at: holotree::format_utc(holotree::unix_now()),
+    }
+    .write(env_path)
+}
+
+/// Whether a lockfile was compiled with `--generate-hashes`
+fn lock_has_hashes(lock_path: &Path) -> Result<bool> {
+    let content = fs::read_to_string(lock_path).with_context(|| format!("Failed to read {:?}", lock_path))?;
+    Ok(content.lines().any(|line| line.trim_start().starts_with("--hash=")))
+}
+
+/// Asks uv which interpreter satisfies the blueprint's `python` and returns its exact version
+/// (e.g. "3.12.4"). With `allow_install`, a matching interpreter is downloaded when none is
+/// present, unless the blueprint only allows system interpreters.
+pub fn resolve_python(uv: &Path, blueprint: &config::Blueprint, allow_install: bool) -> Result<String> {
+    let spec = blueprint.python.as_str();
+    let find = || {
+        let mut find = Command::new(uv);
+        find.args(["python", "find", spec]);
+        blueprint.python_source(&mut find);
+        find.output()
+    };
+
+    let mut found = find()?;
+    if !found.status.success() && blueprint.system_python_only() {
+        anyhow::bail!(
+            "No installed Python satisfies '{}', and python_preference is only-system, so none will be downloaded",
+            spec
+        );
+    }
+    if !found.status.success() && allow_install {
+        status!("{} Installing a Python matching {}...", "🐍".magenta(), spec);
+        let mut install = Command::new(uv);
+        install.args(["python", "install", spec]).stdout(ui::child_stdout());
+        blueprint.python_source(&mut install);
+        if ui::quiet() {
+            install.arg("--quiet");
+        }
+        ui::trace_command(&install);
+        let status = interrupt::status(&mut install)?;
+        if status.success() {
+            found = find()?;
+        }
+    }
+    if !found.status.success() {
+        anyhow::bail!("No Python interpreter satisfies '{}'", spec);
+    }
+
+    let interpreter = String::from_utf8_lossy(&found.stdout).trim().to_string();
+    let version = Command::new(&interpreter)
+        .args(["-c", "import platform; print(platform.python_version())"])
+        .output()
+        .with_context(|| format!("Failed to query {}", interpreter))?;
+    if !version.status.success() {
+        anyhow::bail!("Interpreter {} did not report its version", interpreter);
+    }
+    Ok(String::from_utf8_lossy(&version.stdout).trim().to_string())
+}
+
+fn print_indexes(blueprint: &config::Blueprint) {
+    for url in blueprint.redacted_indexes() {
+        status!("{} Using index: {}", "🌐".blue(), url);
+    }
+}
+
+fn build_env(
+    uv: &Path,
+    env_path: &Path,
+    req_file: &Path,
+    blueprint: &config::Blueprint,
+    opts: &EnvOptions,
+    deadline: &BuildDeadline,
+) -> Result<()> {
+    let python_version = &blueprint.python;
+    let loose = config::is_blueprint_file(req_file);
+
+    // Hash-pinned locks are installed in --require-hashes mode; decide before building anything
+    let hashed = !loose && lock_has_hashes(req_file)?;
+    if blueprint.require_hashes && loose {
+        anyhow::bail!("require_hashes is set but there is no lockfile. Run `cask lock` first.");
+    }
+    if blueprint.require_hashes && !hashed {
+        anyhow::bail!(
+            "require_hashes is set but {} has no package hashes. Run `cask lock` to regenerate it.",
+            req_file.display()
+        );
+    }
+
+    fs::create_dir_all(env_path)?;
+    let started = Instant::now();
+    let mut phases = Phases::default();
+
+    // A+B. Warm-start from this project's previous env when building from a lock; else from scratch
+    let warm = Instant::now();
+    if !loose && warm_start(uv, env_path, req_file, blueprint, hashed, deadline)? {
+        phases.record("warm start", warm.elapsed());
+    } else {
+        // A. Create Venv
+        status!("{} Fetching Python {}...", "🐍".magenta(), python_version);
+        phases.time("venv", || {
+            if blueprint.system_python_only() {
+                // uv would say the same, but only after a less obvious "no interpreter found"
+                resolve_python(uv, blueprint, false)?;
+            }
+            // Relocatable, so entry points and activate scripts survive the rename out of staging.
+            // uv leaves out the system site-packages unless asked, which also disables the user's.
+            let mut venv = Command::new(uv);
+            venv.arg("venv")
+                .arg(holotree::venv_root(env_path))
+                .arg("--relocatable")
+                .arg("--python")
+                .arg(python_version)
+                .args(offline_args())
+                .current_dir(env_path);
+            blueprint.python_source(&mut venv);
+            let status = run_uv_step(&mut venv, "Creating virtual environment", deadline)?;
+            if !status.success() { anyhow::bail!("Failed to create venv"); }
+            Ok(())
+        })?;
+
+        // B. Install Dependencies
+        status!("{} Installing dependencies...", "📦".magenta());
+        phases.time("install", || {
+            let (install_target, label) = if loose {
+                // Convert the blueprint -> temp requirements.txt
+                let temp_req = env_path.join("temp_reqs.txt");
+                let requirements = blueprint.to_requirements_txt(opts.dev, &opts.extras)?;
+                fs::write(&temp_req, &requirements)?;
+                let count = lockfile::pins(&requirements).len();
+                (temp_req, format!("Installing {} requirement(s) and their dependencies", count))
+            } else {
+                // Lockfile: Must use absolute path because we change CWD
+                let count = lockfile::pins(&fs::read_to_string(req_file)?).len();
+                (holotree::canonical(req_file)?, format!("Installing {} locked package(s)", count))
+            };
+
+            print_indexes(blueprint);
+            let mut install = uv_pip(uv, env_path, "install");
+            install.arg("-r")
+                .arg(&install_target)
+                .args(blueprint.constraint_args())
+                .args(offline_args())
+                .args(blueprint.index_args())
+                .args(&blueprint.uv_args);
+            if hashed {
+                install.arg("--require-hashes");
+            }
+            if frozen_lockfile() {
+                install.arg("--strict"); // Also fail on a lock whose packages' dependencies aren't all pinned
+            }
+            let status = install_with_retries(&mut install, &label, deadline)?;
+
+            if loose {
+                let _ = fs::remove_file(&install_target);
+            }
+
+            if !status.success() { anyhow::bail!("Failed to install dependencies"); }
+            Ok(())
+        })?;
+    }
+
+    // C. Post-install hooks, run with the venv activated
+    if !blueprint.post_install.is_empty() {
+        phases.time("post-install", || {
+            for hook in &blueprint.post_install {
+                status!("{} Running post-install: {}", "🪝".magenta(), hook);
+                let argv = config::split_command(hook)?;
+                let mut command = venv_command(env_path, &argv)?;
+                command.current_dir(env_path);
+                let status = run_uv_step(&mut command, hook, deadline)?;
+                if !status.success() {
+                    anyhow::bail!("post_install command failed ({}): {}", status, hook);
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    // D. Record what actually got installed
+    let freeze = deadline.output(&mut uv_pip(uv, env_path, "freeze")).context("Listing installed packages failed")?;
+    if !freeze.status.success() { anyhow::bail!("Failed to list installed packages"); }
+    let installed = String::from_utf8_lossy(&freeze.stdout);
+    // Catches whatever the lock didn't: loose builds and packages pulled in by post_install
+    config::GlobalConfig::get()?.check_packages(installed.lines(), "The environment")?;
+    holotree::write_manifest(env_path, &installed)?;
+
+    status!("{} Built in {:.1}s ({})", "⏱️".cyan(), started.elapsed().as_secs_f64(), phases.summary());
+    Ok(())
+}
+
+/// Runs the install step until it succeeds, up to CASK_DOWNLOAD_RETRIES times. The venv
+/// (and its interpreter) stays in place between attempts, so a PyPI hiccup costs one more
+/// install rather than a cold build. Offline there is no network to be flaky, so no retries.
+fn install_with_retries(install: &mut Command, label: &str, deadline: &BuildDeadline) -> Result<ExitStatus> {
+    let attempts = if bootstrap::offline() { 1 } else { bootstrap::download_attempts()? };
+    let mut backoff = INSTALL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let status = run_uv_step(install, label, deadline)?;
+        if status.success() || attempt >= attempts || interrupt::interrupted() {
+            return Ok(status);
+        }
+        tracing::warn!("{} Install attempt {}/{} failed; retrying in {:?}...", "🔁".yellow(), attempt, attempts, backoff);
+        thread::sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// Where the interpreter of the venv built in `staging` will be once it is moved to
+/// `env_path`. uv knows the layout of the venvs it creates, so it is asked first; the
+/// interpreter then reports its own `sys.executable`, which is kept only if it runs with
+/// the venv as `sys.prefix` (a launcher or shim may name the bare base interpreter).
+fn venv_interpreter(uv: &Path, staging: &Path, env_path: &Path, deadline: &BuildDeadline) -> Result<Option<PathBuf>> {
+    let venv = holotree::venv_root(staging);
+    let mut find = Command::new(uv);
+    find.args(["python", "find"]).env("VIRTUAL_ENV", &venv).current_dir(staging);
+    let found = Some(deadline.output(&mut find)?)
+        .filter(|out| out.status.success())
+        .map(|out| PathBuf::from(String::from_utf8_lossy(&out.stdout).trim()))
+        .filter(|path| path.starts_with(&venv)) // Not some other interpreter uv preferred
+        .or_else(|| holotree::probe_interpreter(staging, holotree::Metadata::read(staging)?.python.as_str()));
+    let Some(found) = found else { return Ok(None) };
+
+    let mut probe = Command::new(&found);
+    probe.args(["-c", "import sys; print(sys.executable); print(sys.prefix)"]);
+    let out = deadline.output(&mut probe)?;
+    if !out.status.success() {
+        return Ok(None);
+    }
+    let out = String::from_utf8_lossy(&out.stdout);
+    let mut lines = out.lines().map(|line| PathBuf::from(line.trim()));
+    let (executable, prefix) = (lines.next().unwrap_or_default(), lines.next().unwrap_or_default());
+    let in_venv = fs::canonicalize(&prefix).ok().is_some_and(|prefix| fs::canonicalize(&venv).is_ok_and(|venv| venv == prefix));
+    let executable = if in_venv && !executable.as_os_str().is_empty() { executable } else { found };
+    let staging = std::path::absolute(staging)?;
+    let located = match executable.strip_prefix(&staging) {
+        Ok(inside) => env_path.join(inside),
+        Err(_) => executable,
+    };
+    Ok(Some(std::path::absolute(&located)?))
+}
+
+/// Wall time of each build phase, for the summary at the end of a build
+#[derive(Default)]
+struct Phases(Vec<(&'static str, Duration)>);
+
+impl Phases {
+    fn time<T>(&mut self, name: &'static str, phase: impl FnOnce() -> Result<T>) -> Result<T> {
+        let started = Instant::now();
+        let result = phase();
+        self.record(name, started.elapsed());
+        result
+    }
+
+    fn record(&mut self, name: &'static str, took: Duration) {
+        self.0.push((name, took));
+    }
+
+    /// "venv 1.2s, install 41.0s, post-install 6.3s"
+    fn summary(&self) -> String {
+        let phases: Vec<String> = self.0.iter().map(|(name, took)| format!("{} {:.1}s", name, took.as_secs_f64())).collect();
+        phases.join(", ")
+    }
+}
+
+/// Seeds `env_path` with a copy of the newest env of the same project and Python, then
+/// syncs it to `lock`. `uv pip sync` rather than install, so packages the lock dropped go
+/// too. Returns false, after cleaning up, if there is no seed or the result doesn't match
+/// the lock; the caller then builds from scratch.
+fn warm_start(
+    uv: &Path,
+    env_path: &Path,
+    lock: &Path,
+    blueprint: &config::Blueprint,
+    hashed: bool,
+    deadline: &BuildDeadline,
+) -> Result<bool> {
+    // A seed's interpreter may be a managed download, which only-system must never use
+    if blueprint.system_python_only() {
+        return Ok(false);
+    }
+    let Some(seed) = blueprint.name.as_deref().and_then(|project| holotree::warm_seed(project, &blueprint.python)) else {
+        return Ok(false);
+    };
+    let seed_hash = seed.file_name().unwrap_or_default().to_string_lossy().to_string();
+    status!("{} Warm-starting from {}...", "♻️".magenta(), seed_hash);
+    match sync_seed(uv, &seed, env_path, lock, blueprint, hashed, deadline) {
+        Ok(()) => Ok(true),
+        Err(e) if interrupt::interrupted() => Err(e),
+        Err(e) => {
+            tracing::warn!("{} Warm start from {} failed ({:#}); building from scratch", "⚠️".yellow(), seed_hash, e);
+            let venv = holotree::venv_root(env_path);
+            if venv.exists() {
+                fs::remove_dir_all(&venv)?;
+            }
+            Ok(false)
+        }
+    }
+}
+
+fn sync_seed(
+    uv: &Path,
+    seed: &Path,
+    env_path: &Path,
+    lock: &Path,
+    blueprint: &config::Blueprint,
+    hashed: bool,
+    deadline: &BuildDeadline,
+) -> Result<()> {
+    holotree::copy_tree(&holotree::venv_root(seed), &holotree::venv_root(env_path)).context("Copying the previous env failed")?;
+
+    status!("{} Syncing dependencies...", "📦".magenta());
+    print_indexes(blueprint);
+    let mut sync = uv_pip(uv, env_path, "sync");
+    sync.arg(holotree::canonical(lock)?)
+        .args(offline_args())
+        .args(blueprint.index_args())
+        .args(&blueprint.uv_args);
+    if hashed {
+        sync.arg("--require-hashes");
+    }
+    if frozen_lockfile() {
+        sync.arg("--strict"); // As strict as a cold install
+    }
+    if !run_uv_step(&mut sync, "Syncing packages", deadline)?.success() {
+        anyhow::bail!("uv pip sync failed");
+    }
+
+    let freeze = deadline.output(&mut uv_pip(uv, env_path, "freeze"))?;
+    if !freeze.status.success() {
+        anyhow::bail!("uv pip freeze failed");
+    }
+    let locked = lockfile::pins(&fs::read_to_string(lock)?);
+    let problems = lockfile::diff(&locked, &lockfile::pins(&String::from_utf8_lossy(&freeze.stdout)));
+    if !problems.is_empty() {
+        anyhow::bail!("the result differs from the lock in {} package(s)", problems.len());
+    }
+    Ok(())
+}
+
+/// `uv pip <subcommand>` aimed at an environment's venv. Passes VIRTUAL_ENV rather than
+/// relying on uv finding `.venv`, so a custom `venv_name` or an activated shell venv can't
+/// redirect it.
+pub fn uv_pip(uv: &Path, env_path: &Path, subcommand: &str) -> Command {
+    let mut command = Command::new(uv);
+    command
+        .args(["pip", subcommand])
+        .current_dir(env_path)
+        .env("VIRTUAL_ENV", holotree::venv_root(env_path));
+    command
+}
+
+/// Runs a uv build step. Verbose mode streams uv's output as-is; otherwise it is
+/// captured behind a spinner and only replayed if the step fails.
+fn run_uv_step(command: &mut Command, label: &str, deadline: &BuildDeadline) -> Result<ExitStatus> {
+    ui::trace_command(command);
+    if ui::verbose() {
+        return deadline.status(command.stdout(ui::child_stdout())).with_context(|| format!("{} failed", label));
+    }
+
+    let pb = if ui::quiet() { ProgressBar::hidden() } else { ProgressBar::new_spinner() };
+    pb.set_style(ProgressStyle::default_spinner().template("   {spinner:.green} {msg} [{elapsed}]")?);
+    pb.set_message(label.to_string());
+    pb.enable_steady_tick(Duration::from_millis(100));
+
+    let output = deadline.output(command);
+    pb.finish_and_clear();
+    let output = output.with_context(|| format!("{} failed", label))?;
+
+    if !output.status.success() {
+        // Through tracing like all other progress, so embedders get it too (the CLI redacts it)
+        for stream in [&output.stdout, &output.stderr] {
+            let text = String::from_utf8_lossy(stream);
+            if !text.trim().is_empty() {
+                tracing::error!("{}", secrets::redact(text.trim_end()));
+            }
+        }
+    }
+    Ok(output.status)
+}
+
+pub fn payload_command(env_path: &Path, payload: &Payload) -> Result<Command> {
+    match payload {
+        Payload::Python(args) => {
+            let mut command = Command::new(holotree::python_path(env_path));
+            command.args(args);
+            command.env("VIRTUAL_ENV", holotree::venv_root(env_path));
+            command.env("PYTHONNOUSERSITE", "1");
+            Ok(command)
+        }
+        Payload::Task { argv, .. } => venv_command(env_path, argv),
+    }
+}
+
+/// A command run as if the venv were activated. The program prefers the venv's own
+/// console script over anything else on PATH. Packages in the user's site-packages
+/// (`pip install --user`) stay invisible, as they will be in production.
+pub fn venv_command(env_path: &Path, argv: &[String]) -> Result<Command> {
+    let exe = if cfg!(windows) { format!("{}.exe", argv[0]) } else { argv[0].clone() };
+    let local = holotree::bin_dir(env_path).join(exe);
+    let mut command = if local.exists() { Command::new(local) } else { Command::new(&argv[0]) };
+    command.args(&argv[1..]);
+    command.env("PATH", venv_path_var(env_path)?);
+    command.env("VIRTUAL_ENV", holotree::venv_root(env_path));
+    command.env("PYTHONNOUSERSITE", "1");
+    Ok(command)
+}
+
+/// PATH with the venv's bin/Scripts first, so `python`, `pip` & console scripts resolve to it
+pub fn venv_path_var(env_path: &Path) -> Result<std::ffi::OsString> {
+    let mut paths = vec![holotree::bin_dir(env_path)];
+    if let Some(existing) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&existing));
+    }
+    Ok(std::env::join_paths(paths)?)
+}
+
+/// Where and how `run_task` launches the payload
+#[derive(Default, Clone)]
+pub struct RunOptions {
+    /// Directory to start in; None keeps the current one
+    pub cwd: Option<PathBuf>,
+    /// Loaded after the project's .env, in order
+    pub env_files: Vec<PathBuf>,
+    /// Prefix every output line with this label
+    pub prefix: Option<String>,
+    /// Also write the output to a timestamped log in this directory
+    pub capture: Option<PathBuf>,
+    /// With `capture`, how many logs to retain there (oldest are deleted)
+    pub keep: Option<usize>,
+    /// Stop the payload once it has run this long
+    pub timeout: Option<Duration>,
+    /// Set last, so they win over .env and `env_files`
+    pub env: Vec<(String, String)>,
+    /// Skip .env entries for variables cask's own environment already has (CI secrets),
+    /// instead of letting the file override them
+    pub dotenv_keep_existing: bool,
+    /// Dump the payload's full environment (secrets masked) to stderr before starting it
+    pub print_env: bool,
+}
+
+/// Runs `payload` inside a prepared environment with .env, `env_files` and `required_env` applied
+pub fn run_task(env: &PreparedEnv, payload: &Payload, opts: &RunOptions) -> Result<ExitStatus> {
+    let span = tracing::info_span!("run", hash = %env.env_hash, python = %env.blueprint.python, exit_code = Empty).entered();
+    let env_path = &env.env_path;
+    holotree::touch(env_path)?;
+
+    match payload {
+        Payload::Python(args) => status!("{} Launching payload: '{}' \n", "🚀".red(), args.join(" ")),
+        Payload::Task { name, argv } => status!("{} Launching task '{}': {} \n", "🚀".red(), name, argv.join(" ")),
+    }
+    let mut command = payload_command(env_path, payload)?;
+    command.env("VIRTUAL_ENV_PROMPT", env.blueprint.venv_prompt());
+    if let Some(dir) = &opts.cwd {
+        command.current_dir(dir);
+    }
+
+    load_dotenv(&mut command, &env.project_root, !opts.dotenv_keep_existing)?;
+    for env_file in &opts.env_files {
+        load_env_file(&mut command, env_file)?;
+    }
+    for (key, value) in &opts.env {
+        secrets::register(value); // Inline values are as likely to be tokens as .env ones
+        command.env(key, value);
+    }
+    check_required_env(&command, &env.blueprint.required_env)?;
+    if opts.print_env {
+        // Straight to stderr, not through status!: it was asked for, even under --quiet
+        eprintln!("Environment of the payload:");
+        for (key, value) in child_env(&command) {
+            eprintln!("   {}={}", key, secrets::redact(&value));
+        }
+    }
+    hooks::fire(Event::RunStart, env)?;
+    ui::trace_command(&command);
+
+    let log = match &opts.capture {
+        Some(dir) => Some(RunLog::create(dir, opts.keep, &command)?),
+        None => None,
+    };
+    if let Some(log) = &log {
+        status!("{} Capturing output to {}", "📝".cyan(), log.path().display());
+    }
+
+    let outcome = if opts.prefix.is_some() || log.is_some() {
+        run_piped(&mut command, opts.prefix.as_deref(), log.as_ref(), opts.timeout)
+    } else {
+        command.spawn().map_err(Into::into).and_then(|mut child| interrupt::wait_payload(&mut child, opts.timeout))
+    };
+    if let Some(log) = &log {
+        match &outcome {
+            Ok(status) => log.finish(status)?,
+            Err(e) => log.abort(e)?,
+        }
+    }
+    if let Some(code) = outcome.as_ref().ok().and_then(ExitStatus::code) {
+        span.record("exit_code", code);
+    }
+    let exit_code = match &outcome {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => exit::code_for(e),
+    };
+    // The payload's own failure outranks a critical hook's
+    let ended = hooks::fire(Event::RunEnd { exit_code }, env);
+    let status = outcome?;
+    ended?;
+    Ok(status)
+}
+
+/// .ENV Injection: applies `<project_root>/.env`, then the encrypted `.env.sops` and
+/// `.env.age`, to the child (whichever exist). Without `override_existing`, variables
+/// already set in cask's environment keep their values.
+pub fn load_dotenv(command: &mut Command, project_root: &Path, override_existing: bool) -> Result<()> {
+    let dotenv_path = project_root.join(".env");
+    if dotenv_path.exists() {
+        apply_env_file(command, &dotenv_path, override_existing)?;
+    }
+    for name in secrets::ENCRYPTED_ENV_FILES {
+        let path = project_root.join(name);
+        if path.exists() {
+            status!("{} Decrypting secrets from {}", "🔐".yellow(), path.display());
+            let plaintext = secrets::decrypt_env_file(&path)?;
+            apply_env_items(command, &path, dotenvy::from_read_iter(plaintext.as_slice()), override_existing)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fails fast, before spawning, if a `required_env` variable is unset or empty for the
+/// child: neither given to it (.env, --env-file) nor inherited from cask's environment
+pub fn check_required_env(command: &Command, required: &[String]) -> Result<()> {
+    let overrides: std::collections::HashMap<_, _> = command.get_envs().collect();
+    let missing: Vec<&str> = required
+        .iter()
+        .filter(|name| {
+            let value = match overrides.get(std::ffi::OsStr::new(name.as_str())) {
+                Some(value) => value.map(|v| v.to_os_string()),
+                None => std::env::var_os(name),
+            };
+            value.is_none_or(|v| v.is_empty())
+        })
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        bail_as!(Failure::Config,
+            "Missing required environment variable(s): {} (set them in the environment, .env or an --env-file)",
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// The variables `command` will start with, sorted: cask's own, as changed by its `env` calls
+pub fn child_env(command: &Command) -> BTreeMap<String, String> {
+    let mut vars: BTreeMap<String, String> = std::env::vars_os()
+        .map(|(key, value)| (key.to_string_lossy().into_owned(), value.to_string_lossy().into_owned()))
+        .collect();
+    for (key, value) in command.get_envs() {
+        let key = key.to_string_lossy().into_owned();
+        match value {
+            Some(value) => vars.insert(key, value.to_string_lossy().into_owned()),
+            None => vars.remove(&key),
+        };
+    }
+    vars
+}
+
+pub fn load_env_file(command: &mut Command, path: &Path) -> Result<()> {
+    apply_env_file(command, path, true)
+}
+
+fn apply_env_file(command: &mut Command, path: &Path, override_existing: bool) -> Result<()> {
+    status!("{} Loading secrets from {}", "🔑".yellow(), path.display());
+    let items = dotenvy::from_path_iter(path).with_context(|| format!("Failed to read {:?}", path))?;
+    apply_env_items(command, path, items, override_existing)
+}
+
+/// Sets each parsed `KEY=value` on the child and registers the value for redaction
+fn apply_env_items<R: std::io::Read>(command: &mut Command, path: &Path, items: dotenvy::Iter<R>, override_existing: bool) -> Result<()> {
+    for item in items {
+        let (key, val) = item.with_context(|| format!("Failed to parse {:?}", path))?;
+        if !override_existing && std::env::var_os(&key).is_some() {
+            tracing::debug!("   {} is already set; ignoring its value from {}", key, path.display());
+            continue;
+        }
+        secrets::register(&val);
+        command.env(key, val);
+    }
+    Ok(())
+}
+
+/// Streams the child's stdout/stderr line by line, tagging each line with `label`
+/// and copying it into `log`
+fn run_piped(command: &mut Command, label: Option<&str>, log: Option<&RunLog>, timeout: Option<Duration>) -> Result<ExitStatus> {
+    use std::io::{BufRead, BufReader};
+
+    // Python block-buffers pipes, which would defeat line-by-line streaming
+    command.env("PYTHONUNBUFFERED", "1");
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+
+    let stdout = child.stdout.take().context("Child stdout was not captured")?;
+    let stderr = child.stderr.take().context("Child stderr was not captured")?;
+
+    let tag = label.map(|label| format!("[{}] ", label)).unwrap_or_default();
+    let forward = |stream: &mut dyn BufRead, to_stderr: bool| {
+        for line in stream.split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\r');
+            if to_stderr {
+                eprintln!("{}{}", tag, line);
+            } else {
+                println!("{}{}", tag, line);
+            }
+            if let Some(log) = log {
+                log.line(line);
+            }
+        }
+    };
+
+    thread::scope(|scope| {
+        scope.spawn(|| forward(&mut BufReader::new(stdout), false));
+        scope.spawn(|| forward(&mut BufReader::new(stderr), true));
+        interrupt::wait_payload(&mut child, timeout)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Sandbox;
+
+    const HASHED_LOCK: &str = include_str!("../tests/fixtures/hashed.lock");
+    const CONFIG: &str = "python: \"3.11\"\ndependencies:\n  - requests\n";
+
+    /// Polls `condition` for up to ten seconds
+    fn wait_for(what: &str, condition: impl Fn() -> bool) {
+        let started = Instant::now();
+        while !condition() {
+            assert!(started.elapsed() < Duration::from_secs(10), "timed out waiting for {}", what);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn holotree_entries() -> Vec<String> {
+        let Ok(entries) = fs::read_dir(holotree::root().unwrap()) else { return Vec::new() };
+        entries.map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect()
+    }
+
+    /// A lock for `config` as `cask lock` would leave it, header included
+    fn locked(sandbox: &Sandbox, config: &Path) -> PathBuf {
+        let blueprint = config::Blueprint::load(config).unwrap();
+        let lock = sandbox.write("project/cask.lock", "requests==2.31.0\n");
+        let inputs = identity::lock_inputs(&blueprint, &EnvOptions::default()).unwrap();
+        lockfile::write_header(&lock, &blueprint.python, Some(inputs)).unwrap();
+        lock
+    }
+
+    fn stale(config: &Path, lock: &Path) -> bool {
+        let blueprint = config::Blueprint::load(config).unwrap();
+        lock_is_stale(config, &blueprint, lock, &EnvOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn touching_the_config_does_not_stale_the_lock() {
+        let sandbox = Sandbox::new();
+        let config = sandbox.write("project/cask.yaml", CONFIG);
+        let lock = locked(&sandbox, &config);
+
+        let later = fs::metadata(&lock).unwrap().modified().unwrap() + Duration::from_secs(60);
+        fs::File::options().write(true).open(&config).unwrap().set_modified(later).unwrap();
+        assert!(!stale(&config, &lock));
+        assert_eq!(lock_drift(&config, &lock, &EnvOptions::default()).unwrap(), None);
+    }
+
+    #[test]
+    fn config_edited_back_is_current_again() {
+        let sandbox = Sandbox::new();
+        let config = sandbox.write("project/cask.yaml", CONFIG);
+        let lock = locked(&sandbox, &config);
+
+        sandbox.write("project/cask.yaml", "python: \"3.11\"\ndependencies:\n  - requests\n  - rich\n");
+        assert!(stale(&config, &lock));
+        sandbox.write("project/cask.yaml", CONFIG);
+        assert!(!stale(&config, &lock));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn concurrent_builds_of_one_hash_build_once() {
+        let sandbox = Sandbox::new();
+        let uv = sandbox.fake_uv(1); // Long enough for both to be past hashing before either is done
+        let config = sandbox.write("project/cask.yaml", CONFIG);
+
+        let builds: Vec<_> = (0..2)
+            .map(|_| {
+                let (uv, config) = (uv.clone(), config.clone());
+                thread::spawn(move || prepare_env(&uv, &config, &EnvOptions::default()))
+            })
+            .collect();
+        let envs: Vec<PreparedEnv> = builds.into_iter().map(|build| build.join().unwrap().unwrap()).collect();
+
+        let installs = sandbox.uv_calls().iter().filter(|call| call.starts_with("pip install")).count();
+        assert_eq!(installs, 1);
+        assert_eq!(envs[0].env_hash, envs[1].env_hash);
+        assert_eq!(envs.iter().filter(|env| env.cached).count(), 1, "one caller reuses the other's build");
+        assert!(holotree::is_complete(&envs[0].env_path));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn paths_with_spaces_still_launch() {
+        let sandbox = Sandbox::named("cask home with spaces ");
+        let uv = sandbox.fake_uv(0);
+        let config = sandbox.write("my project/cask.yaml", CONFIG);
+
+        let env = prepare_env(&uv, &config, &EnvOptions::default()).unwrap();
+        let python = holotree::python_path(&env.env_path);
+        assert!(python.to_string_lossy().contains(' ') && python.starts_with(holotree::root().unwrap()), "{}", python.display());
+        assert!(python.is_file(), "{}", python.display());
+
+        let mut payload = payload_command(&env.env_path, &Payload::Python(vec!["--version".to_string()])).unwrap();
+        assert!(payload.status().unwrap().success());
+        assert!(holotree::python_launches(&env.env_path));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn interrupted_build_leaves_nothing_behind() {
+        let sandbox = Sandbox::new();
+        let uv = sandbox.fake_uv(30);
+        let config = sandbox.write("project/cask.yaml", CONFIG);
+        let dry_run = EnvOptions { dry_run: true, ..Default::default() };
+        let env_hash = prepare_env(&uv, &config, &dry_run).unwrap().env_hash;
+
+        let build = {
+            let (uv, config) = (uv.clone(), config.clone());
+            thread::spawn(move || prepare_env(&uv, &config, &EnvOptions::default()).map(|_| ()))
+        };
+        wait_for("the install step", || sandbox.uv_calls().iter().any(|call| call.starts_with("pip install")));
+        let staging = format!("{}.building-", env_hash);
+        assert!(holotree_entries().iter().any(|name| name.starts_with(&staging)));
+
+        interrupt::simulate();
+        let result = build.join().unwrap();
+        interrupt::reset();
+
+        assert!(result.is_err());
+        let left = holotree_entries();
+        assert!(!left.iter().any(|name| *name == env_hash || name.starts_with(&staging)), "{:?}", left);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn hashed_lock_installs_with_require_hashes() {
+        let sandbox = Sandbox::new();
+        let uv = sandbox.fake_uv(0);
+        let config = sandbox.write("project/cask.yaml", CONFIG);
+        let lock = sandbox.write("project/cask.lock", HASHED_LOCK);
+        assert!(lock_has_hashes(&lock).unwrap());
+
+        prepare_env(&uv, &config, &EnvOptions::default()).unwrap();
+        let calls = sandbox.uv_calls();
+        let install = calls.iter().find(|call| call.starts_with("pip install")).expect("an install step");
+        assert!(install.contains("--require-hashes"), "{}", install);
+        assert!(install.contains(&holotree::canonical(&lock).unwrap().display().to_string()), "{}", install);
+    }
+}
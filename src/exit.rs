@@ -0,0 +1,135 @@
+//! Exit codes, so wrappers can react without parsing stderr:
+//!
+//! | code | meaning                                                  |
+//! |------|----------------------------------------------------------|
+//! | 0    | success                                                  |
+//! | 1    | any other error                                          |
+//! | 2    | config or usage error (bad cask.yaml, missing file, flag)|
+//! | 3    | engine (uv) bootstrap failure                            |
+//! | 4    | lock or environment build failure                        |
+//! | N    | `cask run`: the payload's own non-zero exit code         |
+//! | 124  | `cask run --timeout` expired and the payload was stopped |
+//! | 130  | interrupted (Ctrl-C)                                     |
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Same as coreutils `timeout`, so existing wrappers recognize it
+pub const TIMEOUT_CODE: i32 = 124;
+
+/// Shown in `cask --help`; keep in sync with the table above
+pub const HELP: &str = "Exit codes:
+  0    success
+  1    any other error
+  2    config or usage error
+  3    engine (uv) bootstrap failure
+  4    lock or environment build failure
+  N    cask run: the payload's own exit code
+  124  cask run --timeout expired
+  130  interrupted";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+    Config,
+    Engine,
+    Build,
+}
+
+impl Failure {
+    fn code(self) -> i32 {
+        match self {
+            Failure::Config => 2,
+            Failure::Engine => 3,
+            Failure::Build => 4,
+        }
+    }
+}
+
+/// Tags an error with its `Failure`; transparent when printed
+#[derive(Debug)]
+struct Classified {
+    kind: Failure,
+    inner: anyhow::Error,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl Error for Classified {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+/// The payload ran but failed; `cask run` exits with the same code
+#[derive(Debug)]
+pub struct PayloadFailed(pub i32);
+
+impl fmt::Display for PayloadFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Process exited with error (code {})", self.0)
+    }
+}
+
+impl Error for PayloadFailed {}
+
+/// The payload outlived `cask run --timeout` and was stopped
+#[derive(Debug)]
+pub struct PayloadTimedOut(pub Duration);
+
+impl fmt::Display for PayloadTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Payload timed out after {}s (--timeout)", self.0.as_secs())
+    }
+}
+
+impl Error for PayloadTimedOut {}
+
+pub trait Classify<T> {
+    /// Tags the error with `kind`, unless something deeper already classified it
+    fn classify(self, kind: Failure) -> anyhow::Result<T>;
+}
+
+impl<T> Classify<T> for anyhow::Result<T> {
+    fn classify(self, kind: Failure) -> anyhow::Result<T> {
+        self.map_err(|inner| {
+            if classification(&inner).is_some() {
+                inner
+            } else {
+                Classified { kind, inner }.into()
+            }
+        })
+    }
+}
+
+fn classification(err: &anyhow::Error) -> Option<Failure> {
+    err.chain().find_map(|e| e.downcast_ref::<Classified>().map(|c| c.kind))
+}
+
+/// The process exit code for an error that reached `main`
+pub fn code_for(err: &anyhow::Error) -> i32 {
+    if let Some(payload) = err.chain().find_map(|e| e.downcast_ref::<PayloadFailed>()) {
+        return payload.0;
+    }
+    if err.chain().any(|e| e.is::<PayloadTimedOut>()) {
+        return TIMEOUT_CODE;
+    }
+    classification(err).map(Failure::code).unwrap_or(1)
+}
+
+/// Wraps an arbitrary error with a classification
+pub fn classified(kind: Failure, inner: anyhow::Error) -> anyhow::Error {
+    Classified { kind, inner }.into()
+}
+
+/// `anyhow::bail!` that also sets the exit code, e.g. `bail_as!(Failure::Config, "...")`
+#[macro_export]
+macro_rules! bail_as {
+    ($kind:expr, $($arg:tt)*) => {
+        return Err($crate::exit::classified($kind, anyhow::anyhow!($($arg)*)))
+    };
+}
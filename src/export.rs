@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::bootstrap;
+use crate::holotree;
+
+/// Always the first entry of an export, so import can vet it before unpacking
+const EXPORT_MANIFEST: &str = "cask-export.json";
+/// Archive prefix holding the environment directory itself
+const ENV_DIR: &str = "env";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub hash: String,
+    pub python: String,
+    pub platform: String,
+}
+
+/// Packs a built environment plus its manifest into a `.tar.gz`
+pub fn export_env(env_path: &Path, hash: &str, python: &str, output: &Path) -> Result<()> {
+    let manifest = ExportManifest {
+        hash: hash.to_string(),
+        python: python.to_string(),
+        platform: bootstrap::platform_triple()?,
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+    let file = File::create(output).with_context(|| format!("Failed to create {:?}", output))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    builder.follow_symlinks(false); // venvs link their interpreter; keep the links
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(holotree::unix_now());
+    header.set_cksum();
+    builder.append_data(&mut header, EXPORT_MANIFEST, manifest_json.as_slice())?;
+
+    builder.append_dir_all(ENV_DIR, env_path)?;
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Unpacks an export into the holotree; returns the manifest and final env path
+pub fn import_env(tarball: &Path) -> Result<(ExportManifest, PathBuf)> {
+    let file = File::open(tarball).with_context(|| format!("Failed to open {:?}", tarball))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut entries = archive.entries()?;
+
+    let first = entries.next().context("Archive is empty")??;
+    if first.path()?.as_ref() != Path::new(EXPORT_MANIFEST) {
+        anyhow::bail!("{:?} is not a cask export (missing {})", tarball, EXPORT_MANIFEST);
+    }
+    let manifest: ExportManifest = serde_json::from_reader(first)
+        .with_context(|| format!("Malformed {} in {:?}", EXPORT_MANIFEST, tarball))?;
+
+    let host = bootstrap::platform_triple()?;
+    if manifest.platform != host {
+        anyhow::bail!(
+            "Environment was exported on {} and cannot run on this host ({})",
+            manifest.platform, host
+        );
+    }
+    if manifest.hash.is_empty() || !manifest.hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid environment hash '{}' in export", manifest.hash);
+    }
+
+    let root = holotree::root()?;
+    let env_path = root.join(&manifest.hash);
+    if holotree::is_complete(&env_path) {
+        anyhow::bail!("Environment {} is already present at {:?}", manifest.hash, env_path);
+    }
+    fs::create_dir_all(&root)?;
+
+    // Unpack beside the holotree and move into place only once complete
+    let staging = tempfile::tempdir_in(&root)?;
+    for entry in entries {
+        entry?.unpack_in(staging.path())?;
+    }
+
+    let unpacked = staging.path().join(ENV_DIR);
+    if !holotree::is_complete(&unpacked) {
+        anyhow::bail!("Export is incomplete, or its Python interpreter is not available on this host");
+    }
+    if env_path.exists() {
+        fs::remove_dir_all(&env_path)?; // Leftover from an interrupted build
+    }
+    fs::rename(&unpacked, &env_path)?;
+
+    Ok((manifest, env_path))
+}
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Directories whose contents may be rewritten at runtime; sharing an inode there
+/// would let one environment's writes leak into every other
+#[cfg_attr(not(unix), allow(dead_code))]
+const MUTABLE_DIRS: &[&str] = &["__pycache__", ".cache", "cache", "tmp", "var", "log", "logs", "etc"];
+
+/// What `deduplicate` did (or would do, on a dry run)
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub files_scanned: usize,
+    pub files_linked: usize,
+    /// Duplicates kept as copies because linking failed (e.g. no hardlink support)
+    pub files_skipped: usize,
+    pub bytes_reclaimed: u64,
+}
+
+/// Replaces byte-identical files across the given venvs with hardlinks to one copy
+#[cfg(unix)]
+pub fn deduplicate(venvs: &[PathBuf], dry_run: bool) -> Result<GcReport> {
+    unix::deduplicate(venvs, dry_run)
+}
+
+#[cfg(not(unix))]
+pub fn deduplicate(_venvs: &[PathBuf], _dry_run: bool) -> Result<GcReport> {
+    anyhow::bail!("cask gc relies on Unix hardlink semantics and is not available on this platform")
+}
+
+#[cfg(unix)]
+mod unix {
+    use anyhow::{Context, Result};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::fs::{self, File};
+    use std::io;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+
+    use super::{GcReport, MUTABLE_DIRS};
+
+    /// Files can only share an inode if they live on the same device and agree on size and mode
+    #[derive(Hash, PartialEq, Eq)]
+    struct Bucket {
+        dev: u64,
+        len: u64,
+        mode: u32,
+    }
+
+    /// Paths per inode, so files that are already linked are hashed and counted once
+    type Inodes = HashMap<u64, Vec<PathBuf>>;
+
+    pub fn deduplicate(venvs: &[PathBuf], dry_run: bool) -> Result<GcReport> {
+        let mut report = GcReport::default();
+        let mut buckets: HashMap<Bucket, Inodes> = HashMap::new();
+        for venv in venvs {
+            collect_files(venv, &mut buckets, &mut report)?;
+        }
+
+        for (bucket, inodes) in buckets {
+            if inodes.len() < 2 {
+                continue;
+            }
+            // Equal size is only a hint; link strictly by content
+            let mut by_digest: HashMap<[u8; 32], Vec<Vec<PathBuf>>> = HashMap::new();
+            for paths in inodes.into_values() {
+                let digest = file_digest(&paths[0])?;
+                by_digest.entry(digest).or_default().push(paths);
+            }
+
+            for mut copies in by_digest.into_values() {
+                // Keep the most-linked inode; every other copy becomes a link to it
+                copies.sort_by_key(|paths| std::cmp::Reverse(paths.len()));
+                let source = copies[0][0].clone();
+                for paths in &copies[1..] {
+                    let mut all_linked = true;
+                    for target in paths {
+                        if dry_run || link_over(&source, target).is_ok() {
+                            report.files_linked += 1;
+                        } else {
+                            report.files_skipped += 1;
+                            all_linked = false;
+                        }
+                    }
+                    // The old inode is only freed once none of its paths remain
+                    if all_linked {
+                        report.bytes_reclaimed += bucket.len;
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn collect_files(dir: &Path, buckets: &mut HashMap<Bucket, Inodes>, report: &mut GcReport) -> Result<()> {
+        for entry in fs::read_dir(dir).with_context(|| format!("Failed to scan {:?}", dir))? {
+            let entry = entry?;
+            let path = entry.path();
+            let meta = fs::symlink_metadata(&path)?;
+
+            if meta.is_dir() {
+                let mutable = MUTABLE_DIRS.iter().any(|name| entry.file_name() == *name);
+                if !mutable {
+                    collect_files(&path, buckets, report)?;
+                }
+            } else if meta.is_file() && meta.len() > 0 {
+                report.files_scanned += 1;
+                let bucket = Bucket { dev: meta.dev(), len: meta.len(), mode: meta.mode() };
+                buckets.entry(bucket).or_default().entry(meta.ino()).or_default().push(path);
+            }
+        }
+        Ok(())
+    }
+
+    fn file_digest(path: &Path) -> Result<[u8; 32]> {
+        let mut file = File::open(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Swaps `target` for a hardlink to `source` via rename, so readers never see it missing.
+    /// Fails (leaving the copy untouched) if the filesystem can't link.
+    fn link_over(source: &Path, target: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(source)?.dev() != fs::symlink_metadata(target)?.dev() {
+            return Err(io::Error::other("files are on different devices"));
+        }
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let staging = target.with_file_name(format!(".{}.cask-gc", name));
+        fs::hard_link(source, &staging)?;
+        fs::rename(&staging, target).inspect_err(|_| {
+            let _ = fs::remove_file(&staging);
+        })
+    }
+}
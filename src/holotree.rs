@@ -0,0 +1,787 @@
+use anyhow::{Context, Result};
+use colored::*;
+use directories::BaseDirs;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::status;
+
+/// Sidecar written into every environment at build time
+const METADATA_FILE: &str = "cask-meta.json";
+/// Touched on every `cask run` so `cask prune` knows what's still in use
+const LAST_USED_FILE: &str = ".last_used";
+/// `uv pip freeze` output captured at the end of a build
+const MANIFEST_FILE: &str = "cask-manifest.txt";
+/// Written last; an environment without it never finished building
+const COMPLETE_MARKER: &str = ".complete";
+/// Build audit record, see `Provenance`
+const PROVENANCE_FILE: &str = "provenance.json";
+/// Builds happen in `<hash>.building-<pid>` and are renamed into place when complete
+const STAGING_MARKER: &str = ".building-";
+/// Holotree subdirectory with human-readable links to environments, see `link_alias`
+pub const ALIAS_DIR: &str = "by-name";
+/// Directory of the venv inside an environment unless the blueprint's `venv_name` says otherwise
+pub const DEFAULT_VENV_NAME: &str = ".venv";
+
+/// Cask's state directory: engine binaries, holotree and caches.
+/// `CASK_HOME` relocates it, e.g. onto a persistent CI cache volume; defaults to ~/.cask.
+pub fn cask_home() -> Result<PathBuf> {
+    if let Some(home) = std::env::var_os("CASK_HOME").filter(|v| !v.is_empty()) {
+        let home = PathBuf::from(home);
+        if !home.is_absolute() {
+            anyhow::bail!("CASK_HOME must be an absolute path, got {:?}", home);
+        }
+        return Ok(home);
+    }
+    let base_dirs = BaseDirs::new().context("No home dir")?;
+    Ok(base_dirs.home_dir().join(".cask"))
+}
+
+/// Resolves the state directory and makes sure it can be created
+pub fn ensure_cask_home() -> Result<PathBuf> {
+    let home = cask_home()?;
+    fs::create_dir_all(&home).with_context(|| format!("Cannot create cask home {:?}", home))?;
+    Ok(home)
+}
+
+/// Set to 1 where the holotree is provisioned by a separate job (e.g. a read-only NFS mount):
+/// a missing env is then an error instead of a build, and nothing is written into it
+pub const READONLY_VAR: &str = "CASK_HOLOTREE_READONLY";
+
+pub fn readonly() -> bool {
+    std::env::var(READONLY_VAR).is_ok_and(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+/// Refuses commands that modify the holotree when it is read-only
+pub fn ensure_writable(action: &str) -> Result<()> {
+    if readonly() {
+        anyhow::bail!("Cannot {}: the holotree at {} is read-only ({}=1)", action, root()?.display(), READONLY_VAR);
+    }
+    Ok(())
+}
+
+/// `persistent` or `ephemeral`, like `--storage` (which wins)
+pub const STORAGE_VAR: &str = "CASK_STORAGE";
+
+/// Where this process keeps its holotree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Storage {
+    /// Under CASK_HOME, reused by every later run
+    Persistent,
+    /// A fresh temporary directory, deleted when cask exits
+    Ephemeral,
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "persistent" => Ok(Storage::Persistent),
+            "ephemeral" => Ok(Storage::Ephemeral),
+            _ => Err(format!("unknown storage '{}' (expected persistent or ephemeral)", s)),
+        }
+    }
+}
+
+pub fn storage_from_env() -> Result<Option<Storage>> {
+    match std::env::var(STORAGE_VAR).ok().filter(|v| !v.trim().is_empty()) {
+        Some(value) => Ok(Some(value.parse().map_err(|e| anyhow::anyhow!("Invalid {}: {}", STORAGE_VAR, e))?)),
+        None => Ok(None),
+    }
+}
+
+/// The temporary directory behind ephemeral storage; dropping it deletes everything inside
+static EPHEMERAL: Mutex<Option<tempfile::TempDir>> = Mutex::new(None);
+
+/// With `Ephemeral`, every environment of this process is built from scratch below the
+/// system temp dir (point TMPDIR at a tmpfs for speed), and uv's cache goes there too
+/// unless UV_CACHE_DIR is set. Must come before `configure_uv_cache`.
+pub fn set_storage(storage: Storage) -> Result<()> {
+    if storage == Storage::Persistent {
+        return Ok(());
+    }
+    if readonly() {
+        anyhow::bail!("Ephemeral storage builds its own environments, so it cannot be combined with {}=1", READONLY_VAR);
+    }
+    let dir = tempfile::Builder::new()
+        .prefix("cask-holotree-")
+        .tempdir()
+        .context("Cannot create a temporary holotree")?;
+    tracing::debug!("   Ephemeral holotree: {}", dir.path().display());
+    *EPHEMERAL.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+    Ok(())
+}
+
+fn ephemeral_dir() -> Option<PathBuf> {
+    EPHEMERAL.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|dir| dir.path().to_path_buf())
+}
+
+/// Deletes the ephemeral holotree, if there is one. Statics are never dropped, so every
+/// way out of the process has to call this.
+pub fn discard_ephemeral() {
+    let dir = EPHEMERAL.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(dir) = dir {
+        let path = dir.path().to_path_buf();
+        if let Err(e) = dir.close() {
+            tracing::warn!("{} Could not remove the ephemeral holotree {}: {}", "⚠️".yellow(), path.display(), e);
+        }
+    }
+}
+
+/// Root directory holding all content-addressed environments
+pub fn root() -> Result<PathBuf> {
+    if let Some(dir) = ephemeral_dir() {
+        return Ok(dir.join("holotree"));
+    }
+    Ok(cask_home()?.join("holotree"))
+}
+
+/// uv's download and wheel cache. Shared by every environment so a package is fetched
+/// once per machine (per process with ephemeral storage); an explicit UV_CACHE_DIR still wins.
+pub fn uv_cache_dir() -> Result<PathBuf> {
+    match std::env::var_os("UV_CACHE_DIR").filter(|v| !v.is_empty()) {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => match ephemeral_dir() {
+            Some(dir) => Ok(dir.join("uv-cache")),
+            None => Ok(cask_home()?.join("cache").join("uv")),
+        },
+    }
+}
+
+/// Points every uv invocation of this process (they inherit the environment) at `uv_cache_dir`
+pub fn configure_uv_cache() -> Result<()> {
+    let dir = uv_cache_dir()?;
+    std::env::set_var("UV_CACHE_DIR", &dir);
+    Ok(())
+}
+
+/// Held while an environment is being built. The OS releases the lock when the file
+/// is closed, so a crashed builder never leaves waiters hanging.
+pub struct BuildLock {
+    _file: File,
+}
+
+/// Takes the build lock for `env_hash` (under $CASK_HOME/locks, since the env directory
+/// itself comes and goes), waiting while another cask process holds it
+pub fn lock_build(env_hash: &str) -> Result<BuildLock> {
+    acquire_lock(env_hash, &format!("building {}", env_hash))
+}
+
+/// Takes the machine-wide lock for bootstrapping uv `version`, so concurrent cold starts
+/// download it once and the rest reuse the result
+pub fn lock_engine(version: &str) -> Result<BuildLock> {
+    acquire_lock(&format!("uv-{}", version), &format!("downloading uv {}", version))
+}
+
+/// Takes the machine-wide lock for writing `lock_path`, so concurrent relocks of one
+/// lockfile run one at a time
+pub fn lock_lockfile(lock_path: &Path) -> Result<BuildLock> {
+    let file_name = lock_path.file_name().unwrap_or_default();
+    let dir = lock_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let dir = dir.canonicalize().or_else(|_| std::path::absolute(dir))?;
+    let key = Sha256::digest(dir.join(file_name).to_string_lossy().as_bytes());
+    acquire_lock(&format!("lockfile-{}", &hex::encode(key)[..16]), &format!("relocking {}", lock_path.display()))
+}
+
+/// `activity` completes "Another cask is ..." while waiting
+fn acquire_lock(name: &str, activity: &str) -> Result<BuildLock> {
+    let (path, file) = open_build_lock(name)?;
+    if file.try_lock_exclusive().is_err() {
+        status!("{} Another cask is {}; waiting for it to finish...", "⏳".yellow(), activity);
+        file.lock_exclusive()
+            .with_context(|| format!("Cannot acquire lock {:?}", path))?;
+    }
+    Ok(BuildLock { _file: file })
+}
+
+fn open_build_lock(name: &str) -> Result<(PathBuf, File)> {
+    let dir = cask_home()?.join("locks");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.lock", name));
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Cannot open build lock {:?}", path))?;
+    Ok((path, file))
+}
+
+/// Scratch directory this process builds `env_hash` in. The final env path only ever
+/// appears through a rename, so a visible environment is always a finished one.
+pub fn staging_path(env_hash: &str) -> Result<PathBuf> {
+    Ok(root()?.join(format!("{}{}{}", env_hash, STAGING_MARKER, std::process::id())))
+}
+
+/// Removes staging directories left by crashed or killed builds. One whose build lock
+/// can be taken has no live builder, so in-progress builds are never touched.
+pub fn sweep_stale_builds() -> Result<usize> {
+    if readonly() {
+        return Ok(0);
+    }
+    let Ok(entries) = fs::read_dir(root()?) else { return Ok(0) };
+    let mut removed = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some((env_hash, _)) = name.split_once(STAGING_MARKER) else { continue };
+        let (_, file) = open_build_lock(env_hash)?;
+        if file.try_lock_exclusive().is_ok() {
+            fs::remove_dir_all(entry.path()).with_context(|| format!("Cannot remove stale build {:?}", entry.path()))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Environment directories are named by their (hex) hash; anything else in the
+/// holotree is housekeeping: aliases, staging dirs, imports being unpacked
+fn is_env_dir(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// What we know about the project that produced an environment
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Metadata {
+    pub project: Option<String>,
+    pub python: String,
+    /// The range from the blueprint when `python` was resolved from one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python_spec: Option<String>,
+    /// Unix timestamp (seconds) of when the build finished
+    pub built_at: u64,
+    /// Canonical path of the config it was built from; decides who owns an alias
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<PathBuf>,
+    /// Where the venv lives inside the environment; whoever builds a shared env picks it
+    #[serde(default = "default_venv_name")]
+    pub venv_name: String,
+    /// Absolute path of the interpreter, as the built venv reported it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<PathBuf>,
+}
+
+fn default_venv_name() -> String {
+    DEFAULT_VENV_NAME.to_string()
+}
+
+impl Metadata {
+    pub fn new(project: Option<String>, python: &str, python_spec: Option<String>, config: &Path, venv_name: &str) -> Self {
+        Self {
+            project,
+            python: python.to_string(),
+            python_spec,
+            built_at: unix_now(),
+            config: canonical(config).ok(),
+            venv_name: venv_name.to_string(),
+            interpreter: None,
+        }
+    }
+
+    pub fn with_interpreter(mut self, interpreter: Option<PathBuf>) -> Self {
+        self.interpreter = interpreter;
+        self
+    }
+
+    pub fn write(&self, env_path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(env_path.join(METADATA_FILE), json)?;
+        Ok(())
+    }
+
+    /// Environments built before the sidecar existed simply have none
+    pub fn read(env_path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(env_path.join(METADATA_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+/// How an environment was built. Kept small and field-stable so two records
+/// can be diffed to spot non-deterministic rebuilds.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Provenance {
+    pub env_hash: String,
+    /// sha256 of the lockfile (or cask.yaml in loose mode) the env was built from
+    pub config_sha256: String,
+    pub uv_version: String,
+    pub python: String,
+    pub os: String,
+    pub arch: String,
+    pub host: Option<String>,
+    /// UTC, RFC 3339
+    pub built_at: String,
+}
+
+impl Provenance {
+    pub fn write(&self, env_path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(env_path.join(PROVENANCE_FILE), json + "\n")?;
+        Ok(())
+    }
+
+    pub fn read(env_path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(env_path.join(PROVENANCE_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+/// The exact interpreter version recorded by the venv (pyvenv.cfg), e.g. "3.11.7"
+pub fn venv_python_version(env_path: &Path) -> Option<String> {
+    let cfg = fs::read_to_string(venv_root(env_path).join("pyvenv.cfg")).ok()?;
+    cfg.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        matches!(key.trim(), "version_info" | "version").then(|| value.trim().to_string())
+    })
+}
+
+/// Best-effort name of this machine
+pub fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// A single cached environment, as reported by `cask list`
+#[derive(Debug, Serialize)]
+pub struct EnvInfo {
+    pub hash: String,
+    pub project: Option<String>,
+    pub python: Option<String>,
+    pub size_bytes: u64,
+    /// Unix timestamp (seconds) of the last use, see `last_used`
+    pub last_access: Option<u64>,
+    pub path: PathBuf,
+}
+
+/// Walks the holotree and describes every environment in it
+pub fn list() -> Result<Vec<EnvInfo>> {
+    let root = root()?;
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut envs = Vec::new();
+    for entry in fs::read_dir(&root)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_dir() || !is_env_dir(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+
+        let meta = Metadata::read(&path);
+        let last_access = last_used(&path);
+
+        envs.push(EnvInfo {
+            hash: entry.file_name().to_string_lossy().to_string(),
+            project: meta.as_ref().and_then(|m| m.project.clone()),
+            python: meta.map(|m| m.python),
+            size_bytes: dir_size(&path),
+            last_access,
+            path,
+        });
+    }
+
+    envs.sort_by(|a, b| a.hash.cmp(&b.hash));
+    Ok(envs)
+}
+
+/// Points `by-name/<project>-<python>` at a freshly built environment. The name stays with
+/// the config that claimed it first (later builds of that config move it along); another
+/// project with the same name gets a short hash suffix instead. Returns the alias name.
+pub fn link_alias(env_path: &Path, env_hash: &str) -> Result<Option<String>> {
+    let Some(meta) = Metadata::read(env_path) else { return Ok(None) };
+    let Some(project) = &meta.project else { return Ok(None) };
+    let alias_dir = root()?.join(ALIAS_DIR);
+    fs::create_dir_all(&alias_dir)?;
+
+    let base = sanitize_alias(&format!("{}-{}", project, meta.python));
+    let owner = fs::read_link(alias_dir.join(&base)).ok().map(|target| alias_dir.join(target));
+    let claimed = owner.is_some_and(|target| {
+        target.is_dir() && Metadata::read(&target).is_some_and(|other| other.config != meta.config)
+    });
+    let name = if claimed { format!("{}-{}", base, &env_hash[..env_hash.len().min(8)]) } else { base };
+
+    replace_link(&alias_dir.join(&name), env_hash)?;
+    Ok(Some(name))
+}
+
+/// Removes aliases whose environment is gone; returns how many
+pub fn prune_aliases() -> Result<usize> {
+    let alias_dir = root()?.join(ALIAS_DIR);
+    let Ok(entries) = fs::read_dir(&alias_dir) else { return Ok(0) };
+    let mut removed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        // `is_dir` follows the link, so it is false exactly when the target is missing
+        if !path.is_dir() {
+            remove_link(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn sanitize_alias(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '-' })
+        .collect()
+}
+
+/// Relative, so the alias survives the holotree being moved with CASK_HOME
+#[cfg(unix)]
+fn replace_link(alias: &Path, env_hash: &str) -> Result<()> {
+    let staging = alias.with_extension("cask-tmp");
+    let _ = fs::remove_file(&staging);
+    std::os::unix::fs::symlink(Path::new("..").join(env_hash), &staging)?;
+    // rename swaps the link atomically, so readers never see the alias missing
+    fs::rename(&staging, alias).with_context(|| format!("Cannot create alias {:?}", alias))
+}
+
+/// Junctions need no special privileges, unlike directory symlinks, but must be absolute
+#[cfg(windows)]
+fn replace_link(alias: &Path, env_hash: &str) -> Result<()> {
+    let target = alias.parent().and_then(Path::parent).context("Alias outside the holotree")?.join(env_hash);
+    if fs::symlink_metadata(alias).is_ok() {
+        remove_link(alias)?;
+    }
+    let status = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(alias)
+        .arg(&target)
+        .stdout(std::process::Stdio::null())
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("Cannot create alias {:?}", alias);
+    }
+    Ok(())
+}
+
+/// Deletes the link itself, never the environment behind it
+fn remove_link(alias: &Path) -> Result<()> {
+    // Junctions are removed like directories on Windows
+    fs::remove_file(alias).or_else(|_| fs::remove_dir(alias))?;
+    Ok(())
+}
+
+/// `fs::canonicalize` without the `\\?\` prefix it adds on Windows, which plenty of
+/// Python tooling (and requirement parsers) mishandle. Elsewhere it is canonicalize as-is.
+pub fn canonical(path: &Path) -> std::io::Result<PathBuf> {
+    let path = fs::canonicalize(path)?;
+    #[cfg(windows)]
+    if let Some(plain) = path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        return Ok(match plain.strip_prefix(r"UNC\") {
+            Some(share) => PathBuf::from(format!(r"\\{}", share)),
+            None => PathBuf::from(plain),
+        });
+    }
+    Ok(path)
+}
+
+/// The environment's venv as an absolute path, for VIRTUAL_ENV and PATH.
+/// Passed to children as an OS string, so spaces (C:\Users\First Last) need no quoting.
+/// The directory name comes from the environment's metadata, which a build writes first.
+pub fn venv_root(env_path: &Path) -> PathBuf {
+    let name = Metadata::read(env_path).map_or_else(default_venv_name, |meta| meta.venv_name);
+    let venv = env_path.join(name);
+    std::path::absolute(&venv).unwrap_or(venv)
+}
+
+/// Executables directory of an environment's venv (Scripts on Windows)
+pub fn bin_dir(env_path: &Path) -> PathBuf {
+    venv_root(env_path).join(venv_layout(cfg!(target_os = "windows")).0)
+}
+
+/// A venv's executables directory and interpreter name, on Windows or elsewhere
+fn venv_layout(windows: bool) -> (&'static str, &'static str) {
+    if windows { ("Scripts", "python.exe") } else { ("bin", "python") }
+}
+
+/// Interpreter of an environment: the one recorded at build time while it still exists
+/// (the holotree may have moved with CASK_HOME, or the env come from `cask import`),
+/// else whatever the venv's bin dir has, else the conventional bin/python
+pub fn python_path(env_path: &Path) -> PathBuf {
+    let meta = Metadata::read(env_path);
+    if let Some(interpreter) = meta.as_ref().and_then(|m| m.interpreter.clone()).filter(|p| inside_env(p, env_path)) {
+        return interpreter;
+    }
+    let python = meta.map_or_else(String::new, |m| m.python);
+    probe_interpreter(env_path, &python)
+        .unwrap_or_else(|| bin_dir(env_path).join(venv_layout(cfg!(target_os = "windows")).1))
+}
+
+/// Whether `interpreter` exists within `env_path`; a copied $CASK_HOME records the old tree's.
+/// Only the directory is resolved, since a venv's python is usually a symlink to its base.
+fn inside_env(interpreter: &Path, env_path: &Path) -> bool {
+    let (Some(dir), Some(name)) = (interpreter.parent(), interpreter.file_name()) else { return false };
+    match (dir.canonicalize(), env_path.canonicalize()) {
+        (Ok(dir), Ok(env_path)) => dir.starts_with(env_path) && dir.join(name).exists(),
+        _ => false,
+    }
+}
+
+/// The first interpreter the venv's bin dir has: not every layout ships a plain `python`
+/// (some only have python3 or python3.12)
+pub fn probe_interpreter(env_path: &Path, python: &str) -> Option<PathBuf> {
+    let bin = bin_dir(env_path);
+    let mut names = vec!["python".to_string(), "python3".to_string()];
+    if !python.is_empty() {
+        names.push(format!("python{}", python));
+    }
+    names
+        .iter()
+        .map(|name| bin.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX)))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn write_manifest(env_path: &Path, packages: &str) -> Result<()> {
+    fs::write(env_path.join(MANIFEST_FILE), packages)?;
+    Ok(())
+}
+
+/// Seals a finished build. Must be the very last step of building.
+pub fn mark_complete(env_path: &Path) -> Result<()> {
+    fs::write(env_path.join(COMPLETE_MARKER), unix_now().to_string())?;
+    Ok(())
+}
+
+/// An environment is usable only if its build was sealed and the interpreter is still there
+pub fn is_complete(env_path: &Path) -> bool {
+    env_path.join(COMPLETE_MARKER).exists()
+        && env_path.join(MANIFEST_FILE).exists()
+        && python_path(env_path).exists()
+}
+
+/// Whether the env's interpreter actually runs here. A complete env can still fail this when
+/// $CASK_HOME is shared with a different platform or the base interpreter was replaced.
+pub fn python_launches(env_path: &Path) -> bool {
+    Command::new(python_path(env_path))
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The most recently used complete env of `project` on `python`, to warm-start a rebuild from
+pub fn warm_seed(project: &str, python: &str) -> Option<PathBuf> {
+    let entries = fs::read_dir(root().ok()?).ok()?;
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| is_env_dir(&e.file_name().to_string_lossy()))
+        .map(|e| e.path())
+        .filter(|path| {
+            Metadata::read(path).is_some_and(|m| m.project.as_deref() == Some(project) && m.python == python)
+        })
+        .filter(|path| is_complete(path) && python_launches(path))
+        .max_by_key(|path| last_used(path).unwrap_or(0))
+}
+
+/// Copies a directory tree, recreating symlinks rather than following them (venvs link
+/// their interpreter)
+pub fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let (source, target) = (entry.path(), to.join(entry.file_name()));
+        let kind = entry.file_type()?;
+        if kind.is_dir() {
+            copy_tree(&source, &target)?;
+        } else if kind.is_symlink() {
+            copy_link(&source, &target)?;
+        } else {
+            fs::copy(&source, &target).with_context(|| format!("Failed to copy {:?}", source))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_link(source: &Path, target: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, target)?;
+    Ok(())
+}
+
+/// Creating symlinks needs privileges on Windows; a copy of the target works as well
+#[cfg(windows)]
+fn copy_link(source: &Path, target: &Path) -> Result<()> {
+    fs::copy(source, target)?;
+    Ok(())
+}
+
+/// Records that an environment was just used (not in a read-only holotree, whose
+/// provisioning job decides what stays)
+pub fn touch(env_path: &Path) -> Result<()> {
+    if readonly() {
+        return Ok(());
+    }
+    fs::write(env_path.join(LAST_USED_FILE), unix_now().to_string())?;
+    Ok(())
+}
+
+/// Best guess at when an environment was last used: the `.last_used` stamp,
+/// then the build time, then whatever the filesystem recorded
+pub fn last_used(env_path: &Path) -> Option<u64> {
+    fs::read_to_string(env_path.join(LAST_USED_FILE))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .or_else(|| Metadata::read(env_path).map(|m| m.built_at))
+        .or_else(|| {
+            fs::metadata(env_path)
+                .and_then(|m| m.accessed())
+                .ok()
+                .and_then(to_unix)
+        })
+}
+
+/// Total size of all files below `path` (symlinks are not followed)
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else { return 0 };
+
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| match e.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&e.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Renders a unix timestamp relative to now, e.g. "3h ago"
+pub fn format_age(timestamp: u64) -> String {
+    let secs = unix_now().saturating_sub(timestamp);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Formats a unix timestamp as RFC 3339 UTC, e.g. "2024-05-01T12:00:00Z"
+pub fn format_utc(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // Civil-from-days (Howard Hinnant), valid for any date after 1970
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, secs / 3600, secs % 3600 / 60, secs % 60
+    )
+}
+
+pub fn unix_now() -> u64 {
+    to_unix(SystemTime::now()).unwrap_or(0)
+}
+
+fn to_unix(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Sandbox;
+
+    /// An environment whose metadata names its venv `venv_name`
+    fn env_with_venv(sandbox: &Sandbox, venv_name: &str) -> PathBuf {
+        let env_path = sandbox.path().join("env");
+        fs::create_dir_all(&env_path).unwrap();
+        Metadata::new(None, "3.11", None, Path::new("cask.yaml"), venv_name).write(&env_path).unwrap();
+        env_path
+    }
+
+    #[test]
+    fn custom_venv_name_moves_every_path() {
+        let sandbox = Sandbox::new();
+        let env_path = env_with_venv(&sandbox, "venv");
+        let venv = venv_root(&env_path);
+        assert_eq!(venv, std::path::absolute(env_path.join("venv")).unwrap());
+
+        for (windows, bin, python) in [(false, "bin", "python"), (true, "Scripts", "python.exe")] {
+            let (layout_bin, layout_python) = venv_layout(windows);
+            assert_eq!(venv.join(layout_bin).join(layout_python), venv.join(bin).join(python));
+        }
+        let (bin, python) = venv_layout(cfg!(target_os = "windows"));
+        assert_eq!(bin_dir(&env_path), venv.join(bin));
+        // Nothing recorded and nothing built: the conventional interpreter of the custom venv
+        assert_eq!(python_path(&env_path), venv.join(bin).join(python));
+    }
+
+    #[test]
+    fn interpreters_recorded_outside_the_env_are_ignored() {
+        let sandbox = Sandbox::new();
+        let env_path = env_with_venv(&sandbox, "venv");
+        let own = bin_dir(&env_path).join(format!("python3{}", std::env::consts::EXE_SUFFIX));
+        fs::create_dir_all(own.parent().unwrap()).unwrap();
+        fs::write(&own, "").unwrap();
+        let elsewhere = sandbox.write("old-home/env/venv/bin/python3", "");
+
+        let record = |interpreter: &Path| {
+            Metadata::new(None, "3.11", None, Path::new("cask.yaml"), "venv")
+                .with_interpreter(Some(interpreter.to_path_buf()))
+                .write(&env_path)
+                .unwrap();
+        };
+        record(&elsewhere);
+        assert_eq!(python_path(&env_path), own);
+        record(&std::path::absolute(&own).unwrap());
+        assert_eq!(python_path(&env_path), std::path::absolute(&own).unwrap());
+    }
+
+    #[test]
+    fn envs_without_a_venv_name_use_the_default() {
+        let sandbox = Sandbox::new();
+        let env_path = sandbox.path().join("env");
+        fs::create_dir_all(&env_path).unwrap();
+        fs::write(env_path.join(METADATA_FILE), r#"{"project": null, "python": "3.11", "built_at": 0}"#).unwrap();
+        assert!(venv_root(&env_path).ends_with(DEFAULT_VENV_NAME));
+    }
+
+    #[test]
+    fn probe_finds_versioned_interpreters_in_the_custom_venv() {
+        let sandbox = Sandbox::new();
+        let env_path = env_with_venv(&sandbox, "venv");
+        let bin = bin_dir(&env_path);
+        fs::create_dir_all(&bin).unwrap();
+        let python = bin.join(format!("python3.11{}", std::env::consts::EXE_SUFFIX));
+        fs::write(&python, "").unwrap();
+        assert_eq!(python_path(&env_path), python);
+    }
+}
@@ -0,0 +1,127 @@
+//! Lifecycle hooks from the `[hooks]` table of `$CASK_HOME/config.toml`: external commands
+//! run after a build and around every payload, e.g. to notify chat or register the run.
+//!
+//! ```toml
+//! [hooks]
+//! on_run_end = "notify-ops --channel robots"
+//! on_build = { command = "cmdb-register", critical = true }
+//! ```
+//!
+//! Each gets CASK_EVENT, CASK_HASH and CASK_PROJECT (when the blueprint has a name);
+//! `on_run_end` also gets CASK_EXIT_CODE. A failing hook is only a warning unless
+//! `critical`, in which case it fails the command.
+
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Deserialize;
+use std::process::{Command, Stdio};
+
+use crate::environment::PreparedEnv;
+use crate::{config, interrupt, status, ui};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    pub on_build: Option<Hook>,
+    pub on_run_start: Option<Hook>,
+    pub on_run_end: Option<Hook>,
+}
+
+/// A bare command line, or a table that can also mark it critical
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Hook {
+    Command(String),
+    Detailed(DetailedHook),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetailedHook {
+    pub command: String,
+    /// Fail the build or run when this hook fails, instead of only warning
+    #[serde(default)]
+    pub critical: bool,
+}
+
+impl Hook {
+    fn command(&self) -> &str {
+        match self {
+            Hook::Command(command) => command,
+            Hook::Detailed(hook) => &hook.command,
+        }
+    }
+
+    fn critical(&self) -> bool {
+        matches!(self, Hook::Detailed(DetailedHook { critical: true, .. }))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    /// A new environment was built (never for a cache hit)
+    Build,
+    /// The payload is about to start
+    RunStart,
+    /// The payload finished, failed to start, or timed out
+    RunEnd { exit_code: i32 },
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Build => "on_build",
+            Event::RunStart => "on_run_start",
+            Event::RunEnd { .. } => "on_run_end",
+        }
+    }
+}
+
+/// Runs the hook configured for `event`, if any. Errors only for a critical hook.
+pub fn fire(event: Event, env: &PreparedEnv) -> Result<()> {
+    let hooks = &config::GlobalConfig::get()?.hooks;
+    let hook = match event {
+        Event::Build => &hooks.on_build,
+        Event::RunStart => &hooks.on_run_start,
+        Event::RunEnd { .. } => &hooks.on_run_end,
+    };
+    let Some(hook) = hook else {
+        return Ok(());
+    };
+
+    status!("{} Running {} hook: {}", "🪝".magenta(), event.name(), hook.command());
+    match run(hook.command(), event, env) {
+        Ok(()) => Ok(()),
+        Err(e) if hook.critical() => Err(e.context(format!("Critical {} hook failed", event.name()))),
+        Err(e) => {
+            tracing::warn!("{} {} hook failed: {:#}", "⚠️".yellow(), event.name(), e);
+            Ok(())
+        }
+    }
+}
+
+fn run(command_line: &str, event: Event, env: &PreparedEnv) -> Result<()> {
+    let argv = config::split_command(command_line)?;
+    let (program, args) = argv.split_first().context("The hook command is empty")?;
+    let mut command = Command::new(program);
+    // Like uv's output, follows the status lines so `cask which` and --json keep stdout clean
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(ui::child_stdout())
+        .env("CASK_EVENT", event.name())
+        .env("CASK_HASH", &env.env_hash);
+    if let Some(project) = &env.blueprint.name {
+        command.env("CASK_PROJECT", project);
+    }
+    if let Event::RunEnd { exit_code } = event {
+        command.env("CASK_EXIT_CODE", exit_code.to_string());
+    }
+    ui::trace_command(&command);
+
+    let status = interrupt::status(&mut command).with_context(|| format!("Failed to start `{}`", program))?;
+    if !status.success() {
+        anyhow::bail!("`{}` exited with {}", command_line, status);
+    }
+    Ok(())
+}
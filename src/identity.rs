@@ -0,0 +1,256 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+
+use crate::config::Blueprint;
+use crate::holotree;
+use crate::EnvOptions;
+
+/// Remembered identities, so warm runs skip re-reading large lockfiles
+const CACHE_FILE: &str = "hashes.json";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct CacheEntry {
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    len: u64,
+    hash: String,
+}
+
+/// Computes the content-addressable environment identity.
+/// The file digest is reused from the cache while the file's mtime and length are unchanged.
+pub fn calculate_hash(file_path: &Path, blueprint: &Blueprint, opts: &EnvOptions) -> Result<String> {
+    let stamp = file_stamp(file_path)?;
+    let key = cache_key(file_path, blueprint, opts)?;
+    let cache_path = cache_path();
+
+    let mut cache = cache_path.as_deref().map(load_cache).unwrap_or_default();
+    if let Some(entry) = cache.get(&key) {
+        if (entry.mtime_secs, entry.mtime_nanos, entry.len) == stamp {
+            tracing::debug!("   Identity cache hit for {}", file_path.display());
+            return Ok(entry.hash.clone());
+        }
+    }
+
+    tracing::debug!("   Hashing {} ({} bytes)", file_path.display(), stamp.2);
+    tracing::debug!(
+        "   Identity inputs: python {}, dev {}, extras [{}], constraints {}, index args [{}], platform {}",
+        blueprint.python,
+        opts.dev,
+        opts.extras.join(", "),
+        blueprint.constraints.as_ref().map_or("-".into(), |p| p.display().to_string()),
+        blueprint.index_args().join(" "),
+        platform_fingerprint(),
+    );
+    let content = fs::read(file_path).with_context(|| format!("Failed to read {:?}", file_path))?;
+    let included = included_files(blueprint, opts)?;
+    let constraints = match &blueprint.constraints {
+        Some(path) => Some(fs::read(path).with_context(|| format!("Failed to read {:?}", path))?),
+        None => None,
+    };
+    let mut hasher = Sha256::new();
+    feed_identity(&mut hasher, blueprint, opts, Some(&content));
+    if let Some(constraints) = &constraints {
+        hasher.update(b"constraints:");
+        hasher.update(constraints); // Tightening a transitive pin must rebuild
+    }
+    for (path, content) in &included {
+        hasher.update(format!("requirements:{}:", path.display()).as_bytes());
+        hasher.update(content); // `-r` files are part of the config as far as the env is concerned
+    }
+    let hash = hex::encode(hasher.finalize())[..16].to_string();
+
+    // The cache is only an optimization; failing to persist it is not an error
+    if let Some(cache_path) = cache_path {
+        let (mtime_secs, mtime_nanos, len) = stamp;
+        cache.insert(key, CacheEntry { mtime_secs, mtime_nanos, len, hash: hash.clone() });
+        let _ = save_cache(&cache_path, &cache);
+    }
+
+    Ok(hash)
+}
+
+/// Feeds every identity input into `hasher`. Without `content` this yields a digest of
+/// everything except the file itself, which is what the cache is keyed on.
+fn feed_identity(hasher: &mut Sha256, blueprint: &Blueprint, opts: &EnvOptions, content: Option<&[u8]>) {
+    hasher.update(blueprint.python.as_bytes());
+    if let Some(content) = content {
+        hasher.update(content);
+    }
+    for dep in blueprint.platform_requirements() {
+        hasher.update(dep.as_bytes()); // Only the host's platform group shapes the env
+    }
+    if opts.dev {
+        hasher.update(b"dev"); // Keep dev and prod envs apart even in loose mode
+    }
+    for extra in &opts.extras {
+        hasher.update(b"extra:");
+        hasher.update(extra.as_bytes());
+    }
+    if !blueprint.inherited_from.is_empty() {
+        // The config file alone no longer describes the env; hash what the merge produced
+        hasher.update(b"extends:");
+        let dev: &[String] = if opts.dev { &blueprint.dev_dependencies } else { &[] };
+        let extras = opts.extras.iter().filter_map(|e| blueprint.optional_dependencies.get(e)).flatten();
+        for dep in blueprint.dependencies.iter().chain(dev).chain(extras) {
+            hasher.update(dep.as_bytes());
+        }
+    }
+    for (name, value) in &blueprint.variables {
+        // The file on disk is only the template; what it expanded to decides the env
+        hasher.update(format!("var:{}={}", name, value).as_bytes());
+    }
+    for dep in blueprint.path_dependencies() {
+        hasher.update(b"path:");
+        hasher.update(dep.as_bytes()); // Absolute, so moving the project rebuilds
+    }
+    for hook in &blueprint.post_install {
+        hasher.update(b"post_install:");
+        hasher.update(hook.as_bytes()); // Hooks shape the env as much as packages do
+    }
+    for arg in blueprint.index_args() {
+        hasher.update(arg.as_bytes()); // Switching registries must rebuild, even from a lock
+    }
+    if let Some(preference) = &blueprint.python_preference {
+        hasher.update(format!("python_preference:{}", preference).as_bytes());
+    }
+    if let Some(mirror) = &blueprint.python_mirror {
+        hasher.update(format!("python_mirror:{}", mirror).as_bytes()); // A different interpreter build
+    }
+    for arg in &blueprint.uv_args {
+        hasher.update(b"uv_arg:");
+        hasher.update(arg.as_bytes()); // Installer flags (e.g. --no-binary) change what gets built
+    }
+    // Binary wheels only fit the platform they were built for, so envs are never shared across one
+    hasher.update(platform_fingerprint().as_bytes());
+}
+
+/// Coarse platform identity: OS and arch, plus the C library family on Linux, since glibc
+/// and musl (e.g. Alpine containers) can't load each other's wheels. "linux-x86_64-gnu"
+pub fn platform_fingerprint() -> &'static str {
+    static FINGERPRINT: OnceLock<String> = OnceLock::new();
+    FINGERPRINT.get_or_init(|| {
+        let (os, arch) = (std::env::consts::OS, std::env::consts::ARCH);
+        if os == "linux" {
+            format!("{}-{}-{}", os, arch, linux_libc())
+        } else {
+            format!("{}-{}", os, arch)
+        }
+    })
+}
+
+/// musl systems ship their dynamic loader as /lib/ld-musl-<arch>.so.1; everything else is glibc
+fn linux_libc() -> &'static str {
+    let musl = fs::read_dir("/lib")
+        .map(|entries| entries.filter_map(|e| e.ok()).any(|e| e.file_name().to_string_lossy().starts_with("ld-musl-")))
+        .unwrap_or(false);
+    if musl { "musl" } else { "gnu" }
+}
+
+/// Digest of everything a lock is compiled from: the requirements this selection of
+/// dev/extras expands to, the constraints file, indexes and `uv_args`. Recorded in the lock
+/// header, so drift means these changed, not merely that cask.yaml was touched.
+pub fn lock_inputs(blueprint: &Blueprint, opts: &EnvOptions) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(blueprint.to_requirements_txt(opts.dev, &opts.extras)?.as_bytes());
+    if let Some(path) = &blueprint.constraints {
+        hasher.update(b"constraints:");
+        hasher.update(fs::read(path).with_context(|| format!("Failed to read {:?}", path))?);
+    }
+    for (path, content) in included_files(blueprint, opts)? {
+        hasher.update(format!("requirements:{}:", path.display()).as_bytes());
+        hasher.update(content);
+    }
+    for arg in blueprint.index_args().iter().chain(&blueprint.uv_args) {
+        hasher.update(b"arg:");
+        hasher.update(arg.as_bytes());
+    }
+    Ok(hex::encode(hasher.finalize())[..16].to_string())
+}
+
+fn cache_key(file_path: &Path, blueprint: &Blueprint, opts: &EnvOptions) -> Result<String> {
+    let path = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+    let mut hasher = Sha256::new();
+    feed_identity(&mut hasher, blueprint, opts, None);
+    // The constraints file is hashed by content too, so any edit to it must miss the cache
+    if let Some(constraints) = &blueprint.constraints {
+        hasher.update(format!("{}{:?}", constraints.display(), file_stamp(constraints)?).as_bytes());
+    }
+    for file in blueprint.requirement_files(opts.dev, &opts.extras)? {
+        hasher.update(format!("{}{:?}", file.display(), file_stamp(&file)?).as_bytes());
+    }
+    Ok(format!("{}#{}", path.display(), hex::encode(hasher.finalize())))
+}
+
+/// The `-r` requirements files of this dependency selection, with their contents
+fn included_files(blueprint: &Blueprint, opts: &EnvOptions) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    blueprint
+        .requirement_files(opts.dev, &opts.extras)?
+        .into_iter()
+        .map(|path| fs::read(&path).with_context(|| format!("Failed to read {:?}", path)).map(|content| (path, content)))
+        .collect()
+}
+
+fn file_stamp(file_path: &Path) -> Result<(u64, u32, u64)> {
+    let meta = fs::metadata(file_path).with_context(|| format!("Failed to read {:?}", file_path))?;
+    let mtime = meta.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok((mtime.as_secs(), mtime.subsec_nanos(), meta.len()))
+}
+
+fn cache_path() -> Option<PathBuf> {
+    holotree::cask_home().ok().map(|home| home.join("cache").join(CACHE_FILE))
+}
+
+fn load_cache(path: &Path) -> HashMap<String, CacheEntry> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(path: &Path, cache: &HashMap<String, CacheEntry>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write-then-rename so concurrent runs never read a torn file
+    let tmp = path.with_extension(format!("json.{}", std::process::id()));
+    fs::write(&tmp, serde_json::to_string(cache)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Sandbox;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    fn set_mtime(path: &Path, time: SystemTime) {
+        File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn unchanged_stamp_is_not_read_again() {
+        let sandbox = Sandbox::new();
+        let config = sandbox.write("project/cask.yaml", "dependencies:\n  - requests\n");
+        let blueprint = Blueprint::load(&config).unwrap();
+        let opts = EnvOptions::default();
+        let first = calculate_hash(&config, &blueprint, &opts).unwrap();
+        let modified = fs::metadata(&config).unwrap().modified().unwrap();
+
+        // Same length and mtime but other bytes: only the cache can still answer `first`
+        fs::write(&config, "dependencies:\n  - rEquests\n").unwrap();
+        set_mtime(&config, modified);
+        assert_eq!(calculate_hash(&config, &blueprint, &opts).unwrap(), first);
+
+        // Once the stamp moves, the file is read and hashed again
+        set_mtime(&config, modified + Duration::from_secs(1));
+        assert_ne!(calculate_hash(&config, &blueprint, &opts).unwrap(), first);
+    }
+}
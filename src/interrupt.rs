@@ -0,0 +1,242 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{exit, holotree};
+
+/// How often blocking waits check for Ctrl-C
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a payload gets to react to the terminal's own SIGINT before we forward one
+const FORWARD_GRACE: Duration = Duration::from_millis(500);
+/// How long a payload that hit its `--timeout` gets to exit after SIGTERM before it is killed
+const TERMINATE_GRACE: Duration = Duration::from_secs(10);
+/// Conventional exit code for "terminated by SIGINT"
+pub const EXIT_CODE: i32 = 130;
+
+/// Ctrl-C presses seen while deferring
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+/// Open `Deferred` guards; with none, Ctrl-C exits right away
+static DEFERRING: AtomicUsize = AtomicUsize::new(0);
+
+/// Routes Ctrl-C through `defer` guards instead of killing cask mid-build
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if DEFERRING.load(Ordering::SeqCst) == 0 {
+            holotree::discard_ephemeral();
+            std::process::exit(EXIT_CODE);
+        }
+        INTERRUPTS.fetch_add(1, Ordering::SeqCst);
+    })
+    .context("Failed to install Ctrl-C handler")
+}
+
+/// While the guard lives, Ctrl-C only sets `interrupted()` so the caller can stop
+/// children and clean up before exiting
+pub fn defer() -> Deferred {
+    DEFERRING.fetch_add(1, Ordering::SeqCst);
+    Deferred
+}
+
+pub struct Deferred;
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        DEFERRING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTS.load(Ordering::SeqCst) > 0
+}
+
+/// What the handler does on Ctrl-C under a `defer` guard
+#[cfg(test)]
+pub(crate) fn simulate() {
+    INTERRUPTS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Forgets simulated presses, so later tests start uninterrupted
+#[cfg(test)]
+pub(crate) fn reset() {
+    INTERRUPTS.store(0, Ordering::SeqCst);
+}
+
+/// Starts the CASK_BUILD_TIMEOUT clock (seconds; unset means no limit) for one build
+pub fn build_deadline() -> Result<BuildDeadline> {
+    let Ok(value) = std::env::var("CASK_BUILD_TIMEOUT") else { return Ok(BuildDeadline::NONE) };
+    let secs: u64 = value.trim().parse().ok().filter(|secs| *secs > 0)
+        .with_context(|| format!("CASK_BUILD_TIMEOUT must be a positive number of seconds, got '{}'", value))?;
+    let budget = Duration::from_secs(secs);
+    Ok(BuildDeadline(Some((Instant::now() + budget, budget))))
+}
+
+/// When a build must be done by, and the budget it was given. Handed to every child the
+/// build waits on, so concurrent builds (`--each-python --parallel`) keep their own clocks.
+/// A child still running once the budget is spent is killed and its wait fails.
+#[derive(Clone, Copy)]
+pub struct BuildDeadline(Option<(Instant, Duration)>);
+
+impl BuildDeadline {
+    /// No limit
+    pub const NONE: BuildDeadline = BuildDeadline(None);
+
+    /// `status`, bounded by this deadline
+    pub fn status(&self, command: &mut Command) -> Result<ExitStatus> {
+        wait_until(&mut command.spawn()?, OnInterrupt::Kill, None, *self)
+    }
+
+    /// `output`, bounded by this deadline
+    pub fn output(&self, command: &mut Command) -> Result<Output> {
+        capture(command, *self)
+    }
+
+    /// The budget, once it has run out
+    fn exceeded(&self) -> Option<Duration> {
+        self.0.filter(|(at, _)| Instant::now() >= *at).map(|(_, budget)| budget)
+    }
+}
+
+/// What to do with a running child once Ctrl-C arrives
+#[derive(Clone, Copy)]
+pub enum OnInterrupt {
+    /// Tooling (uv): stop it now, its work is thrown away anyway
+    Kill,
+    /// The payload: let it handle SIGINT and clean up; a second Ctrl-C kills it
+    Forward,
+}
+
+/// Waits for `child`, reacting to Ctrl-C according to `mode`
+pub fn wait_child(child: &mut Child, mode: OnInterrupt) -> Result<ExitStatus> {
+    wait_until(child, mode, None, BuildDeadline::NONE)
+}
+
+/// `wait_child` for the payload, bounded by `timeout`: once it passes, the child is
+/// terminated and the wait fails with `exit::PayloadTimedOut`
+pub fn wait_payload(child: &mut Child, timeout: Option<Duration>) -> Result<ExitStatus> {
+    wait_until(child, OnInterrupt::Forward, timeout.map(|budget| (Instant::now() + budget, budget)), BuildDeadline::NONE)
+}
+
+fn wait_until(
+    child: &mut Child,
+    mode: OnInterrupt,
+    limit: Option<(Instant, Duration)>,
+    deadline: BuildDeadline,
+) -> Result<ExitStatus> {
+    let _defer = defer();
+    let mut interrupted_at: Option<Instant> = None;
+    let mut forwarded = false;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if let Some((_, budget)) = limit.filter(|(at, _)| Instant::now() >= *at) {
+            terminate(child);
+            return Err(exit::PayloadTimedOut(budget).into());
+        }
+        if let Some(budget) = deadline.exceeded() {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("exceeded the build timeout of {}s (CASK_BUILD_TIMEOUT)", budget.as_secs());
+        }
+        let presses = INTERRUPTS.load(Ordering::SeqCst);
+        if presses > 0 {
+            let since = *interrupted_at.get_or_insert_with(Instant::now);
+            match mode {
+                OnInterrupt::Kill => break,
+                OnInterrupt::Forward if presses > 1 => break,
+                // A terminal already signalled the whole process group; only
+                // forward when the child evidently didn't get it (e.g. `kill -INT <cask>`)
+                OnInterrupt::Forward if !forwarded && since.elapsed() >= FORWARD_GRACE => {
+                    send_sigint(child);
+                    forwarded = true;
+                }
+                OnInterrupt::Forward => {}
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    let _ = child.kill(); // It may have exited on its own in the meantime
+    Ok(child.wait()?)
+}
+
+/// `Command::status`, but a Ctrl-C kills the child
+pub fn status(command: &mut Command) -> Result<ExitStatus> {
+    BuildDeadline::NONE.status(command)
+}
+
+/// `Command::output`, but a Ctrl-C kills the child
+pub fn output(command: &mut Command) -> Result<Output> {
+    capture(command, BuildDeadline::NONE)
+}
+
+fn capture(command: &mut Command, deadline: BuildDeadline) -> Result<Output> {
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+
+    // Drain both pipes concurrently so a chatty child can't block on a full buffer
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+    let status = wait_until(&mut child, OnInterrupt::Kill, None, deadline)?;
+    if interrupted() {
+        // Grandchildren may still hold the pipes open; don't wait for them
+        return Ok(Output { status, stdout: Vec::new(), stderr: Vec::new() });
+    }
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Asks `child` to stop with SIGTERM, killing it if it is still around after `TERMINATE_GRACE`
+#[cfg(unix)]
+fn terminate(child: &mut Child) {
+    // SAFETY: as in `send_sigint`
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    let since = Instant::now();
+    while since.elapsed() < TERMINATE_GRACE {
+        if !matches!(child.try_wait(), Ok(None)) {
+            return;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// `Child::kill` is TerminateProcess here; there is no gentler signal to send first
+#[cfg(not(unix))]
+fn terminate(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+fn send_sigint(child: &Child) {
+    // SAFETY: kill(2) has no memory-safety preconditions; a stale pid at worst yields ESRCH
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn send_sigint(_child: &Child) {
+    // Console Ctrl-C events already reach every process attached to the console
+}
@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+
+/// Caps how many `build_env` operations may run at once
+static BUILD_SLOTS: OnceLock<Semaphore> = OnceLock::new();
+
+pub struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// Held for the duration of a build; frees its slot on drop
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            available: Mutex::new(permits.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut available = self.available.lock().unwrap_or_else(|e| e.into_inner());
+        while *available == 0 {
+            available = self.released.wait(available).unwrap_or_else(|e| e.into_inner());
+        }
+        *available -= 1;
+        Permit { semaphore: self }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut available = self.semaphore.available.lock().unwrap_or_else(|e| e.into_inner());
+        *available += 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+/// Sets the build concurrency from `--jobs`, then CASK_BUILD_JOBS, then the CPU count
+pub fn configure(jobs: Option<usize>) -> Result<()> {
+    let jobs = match jobs {
+        Some(n) => n,
+        None => match std::env::var("CASK_BUILD_JOBS") {
+            Ok(value) => value.trim().parse()
+                .with_context(|| format!("CASK_BUILD_JOBS must be a positive integer, got '{}'", value))?,
+            Err(_) => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        },
+    };
+    if jobs == 0 {
+        anyhow::bail!("Build jobs must be at least 1");
+    }
+    let _ = BUILD_SLOTS.set(Semaphore::new(jobs));
+    Ok(())
+}
+
+/// Blocks until a build slot is free
+pub fn build_slot() -> Permit<'static> {
+    BUILD_SLOTS.get_or_init(|| Semaphore::new(1)).acquire()
+}
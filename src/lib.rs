@@ -0,0 +1,86 @@
+//! Cask as a library: the environment pipeline behind the `cask` CLI.
+//!
+//! ```no_run
+//! use cask::{Cask, EnvOptions, Payload, RunOptions};
+//! # fn main() -> anyhow::Result<()> {
+//! let cask = Cask::ensure_engine(None)?;
+//! let env = cask.prepare("robot/cask.yaml".as_ref(), &EnvOptions::default())?;
+//! let payload = Payload::resolve(&env.blueprint, &["tasks.py".to_string()])?;
+//! let status = cask.run(&env, &payload, &RunOptions::default())?;
+//! # Ok(()) }
+//! ```
+//!
+//! Nothing here parses arguments or prints; progress is emitted as `tracing` events,
+//! so callers see it only if they install a subscriber.
+
+pub mod audit;
+pub mod bootstrap;
+pub mod capture;
+pub mod config;
+pub mod environment;
+pub mod exit;
+pub mod export;
+pub mod gc;
+pub mod hooks;
+pub mod holotree;
+pub mod identity;
+pub mod interrupt;
+pub mod jobs;
+pub mod lockfile;
+pub mod secrets;
+pub mod telemetry;
+pub mod templates;
+#[cfg(test)]
+mod testing;
+pub mod ui;
+pub mod watch;
+pub mod workspace;
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+pub use environment::{EnvOptions, Payload, PreparedEnv, RunOptions, Upgrade};
+
+/// Provisions and runs environments with one uv engine
+pub struct Cask {
+    uv: PathBuf,
+}
+
+impl Cask {
+    /// Uses the given uv release (downloading it if needed), else the default engine
+    pub fn ensure_engine(version: Option<&str>) -> Result<Self> {
+        Ok(Self { uv: bootstrap::Engine::ensure(version)?.path })
+    }
+
+    /// Uses an already installed uv binary as-is
+    pub fn with_engine(uv: impl Into<PathBuf>) -> Self {
+        Self { uv: uv.into() }
+    }
+
+    pub fn engine(&self) -> &Path {
+        &self.uv
+    }
+
+    /// The environment hash `config` resolves to, without building or relocking
+    pub fn identity(&self, config: &Path, opts: &EnvOptions) -> Result<String> {
+        let opts = EnvOptions { dry_run: true, ..opts.clone() };
+        Ok(environment::prepare_env(&self.uv, config, &opts)?.env_hash)
+    }
+
+    /// Relocks if the config drifted, then builds the environment unless it is cached
+    pub fn prepare(&self, config: &Path, opts: &EnvOptions) -> Result<PreparedEnv> {
+        environment::prepare_env(&self.uv, config, opts)
+    }
+
+    /// Writes the lockfile for `config` and returns its path
+    pub fn lock(&self, config: &Path, opts: &EnvOptions, upgrade: &Upgrade) -> Result<PathBuf> {
+        environment::lock_dependencies(&self.uv, config, opts, upgrade)?;
+        Ok(environment::lock_path_for(config, opts))
+    }
+
+    /// Runs `payload` in a prepared environment and returns how it exited
+    pub fn run(&self, env: &PreparedEnv, payload: &Payload, opts: &RunOptions) -> Result<ExitStatus> {
+        environment::run_task(env, payload, opts)
+    }
+}
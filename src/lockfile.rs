@@ -0,0 +1,318 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config;
+
+/// Bump when the lock layout changes; older cask versions refuse newer locks
+pub const FORMAT_VERSION: u32 = 1;
+/// First line of every lock, e.g. `# cask-lock v1 python=3.11 os=linux arch=x86_64 inputs=1f3a...`
+const MAGIC: &str = "# cask-lock v";
+
+/// Packages that only install on one OS, for guessing where a headerless lock came from
+const OS_ONLY_PACKAGES: &[(&str, &str)] = &[
+    ("pywin32", "windows"),
+    ("pywinpty", "windows"),
+    ("pyobjc", "macos"),
+    ("pyobjc-core", "macos"),
+];
+
+/// Wheel platform-tag endings, as `std::env::consts::ARCH` names them
+const WHEEL_ARCHES: &[(&str, &str)] = &[
+    ("x86_64", "x86_64"),
+    ("amd64", "x86_64"),
+    ("aarch64", "aarch64"),
+    ("arm64", "aarch64"),
+    ("i686", "x86"),
+    ("win32", "x86"),
+];
+
+/// An OS/arch pair, named like `std::env::consts`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    pub os: String,
+    pub arch: String,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        Self { os: std::env::consts::OS.to_string(), arch: std::env::consts::ARCH.to_string() }
+    }
+}
+
+/// What a lock was compiled for, parsed from its header line.
+/// `version` 0 marks one guessed from the lock's contents (see `infer`).
+#[derive(Debug)]
+pub struct LockHeader {
+    pub version: u32,
+    pub python: Option<String>,
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    /// `identity::lock_inputs` of the config the lock was compiled from
+    pub inputs: Option<String>,
+}
+
+impl LockHeader {
+    /// Header for a lock compiled now, for `python` on this host
+    pub fn current(python: &str, inputs: Option<String>) -> Self {
+        let host = Platform::current();
+        Self {
+            version: FORMAT_VERSION,
+            python: Some(compact(python)),
+            os: Some(host.os),
+            arch: Some(host.arch),
+            inputs,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut line = format!("{}{}", MAGIC, self.version);
+        if let Some(python) = &self.python {
+            line.push_str(&format!(" python={}", python));
+        }
+        if let Some(os) = &self.os {
+            line.push_str(&format!(" os={}", os));
+        }
+        if let Some(arch) = &self.arch {
+            line.push_str(&format!(" arch={}", arch));
+        }
+        if let Some(inputs) = &self.inputs {
+            line.push_str(&format!(" inputs={}", inputs));
+        }
+        line
+    }
+
+    /// Reads the header of a lock. Locks written before headers existed yield `None`;
+    /// locks from a newer cask are an error rather than something to guess at.
+    pub fn read(lock_path: &Path) -> Result<Option<Self>> {
+        let content = fs::read_to_string(lock_path).with_context(|| format!("Failed to read {:?}", lock_path))?;
+        let Some(rest) = content.lines().next().and_then(|l| l.strip_prefix(MAGIC)) else {
+            return Ok(None);
+        };
+
+        let mut fields = rest.split_whitespace();
+        let version: u32 = fields
+            .next()
+            .and_then(|v| v.parse().ok())
+            .with_context(|| format!("Malformed cask-lock header in {}", lock_path.display()))?;
+        if version > FORMAT_VERSION {
+            anyhow::bail!(
+                "{} uses lock format v{}, but this cask only understands up to v{}. Upgrade cask.",
+                lock_path.display(), version, FORMAT_VERSION
+            );
+        }
+
+        let mut header = Self { version, python: None, os: None, arch: None, inputs: None };
+        for field in fields {
+            match field.split_once('=') {
+                Some(("python", value)) => header.python = Some(value.to_string()),
+                Some(("os", value)) => header.os = Some(value.to_string()),
+                Some(("arch", value)) => header.arch = Some(value.to_string()),
+                Some(("inputs", value)) => header.inputs = Some(value.to_string()),
+                _ => {} // Tolerate fields added by later v1 writers
+            }
+        }
+        Ok(Some(header))
+    }
+
+    /// Reads the header of a lock, falling back to `infer` for locks that predate headers
+    pub fn read_or_infer(lock_path: &Path) -> Result<Self> {
+        match Self::read(lock_path)? {
+            Some(header) => Ok(header),
+            None => {
+                let content = fs::read_to_string(lock_path).with_context(|| format!("Failed to read {:?}", lock_path))?;
+                Ok(Self::infer(&content))
+            }
+        }
+    }
+
+    /// Best guess at the platform a headerless lock was compiled on: the platform tags of
+    /// wheels it pins by URL, and packages that only exist on one OS. Lines with environment
+    /// markers are skipped, since a universal lock is meant to be portable. Conflicting
+    /// evidence leaves the field unknown rather than guessing.
+    pub fn infer(content: &str) -> Self {
+        let mut oses = Vec::new();
+        let mut arches = Vec::new();
+        let pins = content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with(['#', '-']));
+        for line in pins.filter(|l| !l.contains(';')) {
+            let name = line.split(['=', '<', '>', '~', '!', '@', ' ', '[']).next().unwrap_or_default().to_lowercase();
+            if let Some((_, os)) = OS_ONLY_PACKAGES.iter().find(|(package, _)| *package == name.replace('_', "-")) {
+                oses.push(os.to_string());
+            }
+            for wheel in line.split_whitespace().filter(|token| token.ends_with(".whl")) {
+                let tags = wheel.rsplit('/').next().unwrap_or(wheel).trim_end_matches(".whl");
+                let Some(platform) = tags.rsplit('-').next() else { continue };
+                for tag in platform.split('.').filter(|tag| *tag != "any") {
+                    let os = if tag.contains("linux") {
+                        "linux"
+                    } else if tag.starts_with("win") {
+                        "windows"
+                    } else if tag.starts_with("macosx") {
+                        "macos"
+                    } else {
+                        continue;
+                    };
+                    oses.push(os.to_string());
+                    if let Some((_, arch)) = WHEEL_ARCHES.iter().find(|(suffix, _)| tag.ends_with(suffix)) {
+                        arches.push(arch.to_string());
+                    }
+                }
+            }
+        }
+
+        let agreed = |mut found: Vec<String>| {
+            found.dedup();
+            (found.len() == 1).then(|| found.remove(0))
+        };
+        Self { version: 0, python: None, os: agreed(oses), arch: agreed(arches), inputs: None }
+    }
+
+    /// Why this lock doesn't fit `python` on this host, if it doesn't
+    pub fn mismatch(&self, python: &str) -> Option<String> {
+        self.mismatch_on(python, &Platform::current())
+    }
+
+    /// Like `mismatch`, against an arbitrary `host`
+    pub fn mismatch_on(&self, python: &str, host: &Platform) -> Option<String> {
+        if let Some(locked) = self.python.as_deref().filter(|p| *p != compact(python)) {
+            return Some(format!("locked for Python {}, config wants {}", locked, python));
+        }
+        let verb = if self.version == 0 { "pins wheels for" } else { "locked on" };
+        let os = self.os.as_deref().unwrap_or(&host.os);
+        let arch = self.arch.as_deref().unwrap_or(&host.arch);
+        if os != host.os || arch != host.arch {
+            return Some(format!("{} {}/{}, this host is {}/{}", verb, os, arch, host.os, host.arch));
+        }
+        None
+    }
+}
+
+/// Prepends the header to a freshly compiled lock. `inputs` is what it was compiled from,
+/// if that is known (see `identity::lock_inputs`).
+pub fn write_header(lock_path: &Path, python: &str, inputs: Option<String>) -> Result<()> {
+    let content = fs::read_to_string(lock_path).with_context(|| format!("Failed to read {:?}", lock_path))?;
+    fs::write(lock_path, format!("{}\n{}", LockHeader::current(python, inputs).render(), content))?;
+    Ok(())
+}
+
+/// One package pinned by a lock or listed by `pip freeze`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    /// The `==` version; None for direct references (`name @ url`) and editables
+    pub version: Option<String>,
+    /// Guarded by an environment marker, so it may legitimately be absent here
+    pub conditional: bool,
+}
+
+/// Packages of a lock or `pip freeze` output by normalized name. Comments, option lines
+/// and hash continuations are skipped; editables are keyed by their path.
+pub fn pins(content: &str) -> BTreeMap<String, Pin> {
+    let mut pins = BTreeMap::new();
+    for line in content.lines().map(str::trim) {
+        let line = line.trim_end_matches('\\').trim();
+        let editable = line.strip_prefix("-e ").map(str::trim);
+        if line.is_empty() || line.starts_with('#') || (line.starts_with('-') && editable.is_none()) {
+            continue;
+        }
+        let (spec, marker) = match editable.unwrap_or(line).split_once(';') {
+            Some((spec, marker)) => (spec.trim(), Some(marker)),
+            None => (editable.unwrap_or(line), None),
+        };
+        let version = match spec.split_once("==") {
+            Some((_, version)) if editable.is_none() && !spec.contains(" @ ") => {
+                version.split_whitespace().next().map(str::to_string)
+            }
+            _ => None,
+        };
+        let name = if editable.is_some() { spec.to_string() } else { config::requirement_name(spec) };
+        pins.insert(name, Pin { version, conditional: marker.is_some() });
+    }
+    pins
+}
+
+/// One way an installed package set departs from a lock
+#[derive(Debug)]
+pub enum Discrepancy {
+    Missing { name: String, pin: Pin },
+    Extra { name: String, pin: Pin },
+    Version { name: String, locked: String, installed: String },
+}
+
+impl Pin {
+    /// "name==version", or just the name for direct references
+    pub fn describe(&self, name: &str) -> String {
+        match &self.version {
+            Some(version) => format!("{}=={}", name, version),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// Compares `pip freeze` pins with a lock's. Packages behind an environment marker may be
+/// absent; everything else must be installed at the locked version, and nothing more.
+pub fn diff(locked: &BTreeMap<String, Pin>, installed: &BTreeMap<String, Pin>) -> Vec<Discrepancy> {
+    let mut found = Vec::new();
+    for (name, pin) in locked {
+        match installed.get(name) {
+            None if pin.conditional => {}
+            None => found.push(Discrepancy::Missing { name: name.clone(), pin: pin.clone() }),
+            Some(have) => {
+                if let (Some(want), Some(have)) = (&pin.version, &have.version) {
+                    if want != have {
+                        found.push(Discrepancy::Version { name: name.clone(), locked: want.clone(), installed: have.clone() });
+                    }
+                }
+            }
+        }
+    }
+    for (name, pin) in installed.iter().filter(|(name, _)| !locked.contains_key(*name)) {
+        found.push(Discrepancy::Extra { name: name.clone(), pin: pin.clone() });
+    }
+    found
+}
+
+/// Header fields are whitespace-separated, so specifiers like ">=3.10, <3.13" are squeezed
+fn compact(python: &str) -> String {
+    python.split_whitespace().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows() -> Platform {
+        Platform { os: "windows".to_string(), arch: "x86_64".to_string() }
+    }
+
+    #[test]
+    fn linux_lock_is_refused_on_windows() {
+        let lock = tempfile::NamedTempFile::new().unwrap();
+        fs::write(lock.path(), "# cask-lock v1 python=3.11 os=linux arch=x86_64 inputs=1f3a\nrequests==2.31.0\n").unwrap();
+        let header = LockHeader::read(lock.path()).unwrap().unwrap();
+        assert_eq!(
+            header.mismatch_on("3.11", &windows()).as_deref(),
+            Some("locked on linux/x86_64, this host is windows/x86_64")
+        );
+        let linux = Platform { os: "linux".to_string(), arch: "x86_64".to_string() };
+        assert_eq!(header.mismatch_on("3.11", &linux), None);
+    }
+
+    #[test]
+    fn headerless_lock_is_judged_by_its_wheels() {
+        let header = LockHeader::infer("numpy @ https://files.example/numpy-1.26.4-cp311-cp311-manylinux_2_17_x86_64.whl\n");
+        assert_eq!(
+            header.mismatch_on("3.11", &windows()).as_deref(),
+            Some("pins wheels for linux/x86_64, this host is windows/x86_64")
+        );
+    }
+
+    #[test]
+    fn python_mismatch_is_reported_first() {
+        let header = LockHeader::current("3.12", None);
+        assert_eq!(
+            header.mismatch_on("3.11", &windows()).as_deref(),
+            Some("locked for Python 3.12, config wants 3.11")
+        );
+    }
+}
@@ -1,157 +1,1121 @@
-mod bootstrap;
-mod config;
-
-use clap::{Parser, Subcommand};
-use sha2::{Digest, Sha256};
+use cask::environment::{
+    check_lock, check_required_env, engine_version, load_dotenv, lock_dependencies, lock_drift, lock_path_for,
+    normalize_extras, payload_command, prepare_env, resolve_python, run_task, uv_pip, venv_path_var,
+};
+use cask::exit::{self, Classify, Failure};
+use cask::lockfile::Discrepancy;
+use cask::{audit, bail_as, bootstrap, config, environment, export, gc, holotree, identity, interrupt, jobs, lockfile, secrets, status, telemetry, templates, ui, watch, workspace};
+use cask::{EnvOptions, Payload, PreparedEnv, RunOptions, Upgrade};
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use directories::BaseDirs;
+use std::process::{Command, ExitStatus};
+use std::time::{Duration, Instant};
 use anyhow::{Context, Result};
 use colored::*;
-use dotenvy; 
 
 #[derive(Parser)]
 #[command(name = "cask")]
 #[command(about = "The High-Performance RPA Environment Manager", long_about = None)]
+#[command(after_help = exit::HELP)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Show full uv output, the commands cask runs, and resolved paths
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Only print errors (and whatever the payload itself prints)
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Maximum number of environment builds to run concurrently (env: CASK_BUILD_JOBS)
+    #[arg(long, global = true)]
+    jobs: Option<usize>,
+
+    /// Log filter, e.g. "debug" or "cask=trace" (defaults to RUST_LOG, then info)
+    #[arg(long, global = true, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Colored output with emoji: auto (only on a terminal, and unless NO_COLOR is set), always, never
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto, value_name = "WHEN")]
+    color: ColorChoice,
+
+    /// Emit status output as newline-delimited JSON events
+    #[arg(long, global = true)]
+    json_logs: bool,
+
+    /// Never touch the network: no engine download, no relocking, uv runs --offline (env: CASK_OFFLINE=1)
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Only ever build from the existing lockfile: fail if it is missing or out of date
+    /// instead of relocking or falling back to loose dependencies, and refuse `cask lock`
+    /// (`--check` still works). Works with --offline, which additionally keeps uv off the
+    /// network (env: CASK_FROZEN_LOCKFILE=1)
+    #[arg(long, global = true)]
+    frozen_lockfile: bool,
+
+    /// Use this uv binary (or name on PATH) instead of cask's own copy (env: CASK_UV_PATH)
+    #[arg(long, global = true, value_name = "PATH")]
+    uv_path: Option<PathBuf>,
+
+    /// Where environments live: persistent (reused across runs) or ephemeral (a temporary
+    /// holotree, built from scratch and deleted on exit) (env: CASK_STORAGE)
+    #[arg(long, global = true, value_name = "persistent|ephemeral")]
+    storage: Option<holotree::Storage>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // Parsed once per process; boxing RunArgs would only complicate the matches
 enum Commands {
     /// Initialize a new Cask project
     Init {
         /// Project name (defaults to current folder name)
         #[arg(short, long)]
         name: Option<String>,
+
+        /// Starter to scaffold: robocorp, playwright, requests-api, selenium or empty;
+        /// or a directory, .tar.gz URL or git URL containing a cask.yaml ({{name}} is filled in)
+        #[arg(short, long, default_value = templates::DEFAULT, value_name = "NAME|PATH|URL")]
+        template: String,
     },
-    /// Execute a command in the isolated environment
-    Run {
-        #[arg(short, long, default_value = "cask.yaml")]
+    /// Execute a command in the isolated environment (at a workspace root: `cask run <member> -- ...`)
+    Run(RunArgs),
+    /// Freeze dependencies into a cask.lock file (at a workspace root: every member's)
+    Lock {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
         config: PathBuf,
 
-        /// The command to run (e.g. "robot.py" or "-m robocorp.tasks ...")
-        /// We allow hyphens so you can pass flags like "-m" or "--verbose" to Python
-        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-        args: Vec<String>,
+        /// Override the Python version from the config (the file on disk is not modified)
+        #[arg(long)]
+        python: Option<String>,
+
+        /// Include dev_dependencies, writing cask.dev.lock instead of cask.lock
+        #[arg(long)]
+        dev: bool,
+
+        /// Optional dependency groups to include, e.g. --extras ocr,pdf
+        #[arg(long, value_delimiter = ',')]
+        extras: Vec<String>,
+
+        /// Exit non-zero if the lockfile is out of date, without writing it
+        #[arg(long)]
+        check: bool,
+
+        /// Let one package (repeatable) move to its newest allowed version; bare `--upgrade`
+        /// re-resolves everything. Otherwise existing pins are kept wherever they still fit.
+        #[arg(long, value_name = "PKG", num_args = 0..=1, default_missing_value = "", conflicts_with = "check")]
+        upgrade: Vec<String>,
+
+        /// Lockfile to write (or --check); defaults to cask.lock next to the config.
+        /// Pass the same path to `cask run --lock`.
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
     },
-    /// Freeze dependencies into a cask.lock file
-    Lock {
-        #[arg(short, long, default_value = "cask.yaml")]
+    /// Describe a project's resolved environment without building it
+    Info {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
         config: PathBuf,
+
+        /// Show the build provenance of a Holotree environment instead
+        #[arg(long, value_name = "HASH")]
+        env: Option<String>,
+    },
+    /// Check the local setup and print a diagnostic report
+    Doctor,
+    /// Print the environment's Python interpreter path (for editor integrations)
+    Which {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+        config: PathBuf,
+
+        /// Fail instead of building (or relocking) when the environment isn't ready yet
+        #[arg(long)]
+        no_build: bool,
     },
-    /// Destroys all environments to reclaim disk space
+    /// Open an interactive shell inside the project's environment
+    Shell {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+        config: PathBuf,
+    },
+    /// Pack a built environment into a portable .tar.gz
+    Export {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+        config: PathBuf,
+
+        /// Archive to write (defaults to <project>-<hash>.tar.gz)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Snapshot the packages actually installed in the built environment into a lockfile
+    Freeze {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+        config: PathBuf,
+
+        /// Lockfile to write (defaults to the cask.lock next to the config)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check that the built environment still has exactly the packages its lockfile pins
+    Verify {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+        config: PathBuf,
+    },
+    /// Check the environment's installed packages against the OSV advisory database
+    Audit {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+        config: PathBuf,
+
+        /// Lowest severity that fails the audit: low, moderate, high or critical.
+        /// Unrated advisories always fail it.
+        #[arg(long, value_name = "SEVERITY", default_value = "low")]
+        fail_on: audit::Severity,
+    },
+    /// Print the requirements cask feeds uv for this project (or, with --locked, the lockfile)
+    Requirements {
+        #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+        config: PathBuf,
+
+        /// Include dev_dependencies
+        #[arg(long)]
+        dev: bool,
+
+        /// Optional dependency groups to include, e.g. --extras ocr,pdf
+        #[arg(long, value_delimiter = ',')]
+        extras: Vec<String>,
+
+        /// Print the resolved pins from the matching lockfile instead
+        #[arg(long)]
+        locked: bool,
+    },
+    /// Unpack an exported environment into the Holotree
+    Import {
+        /// Archive produced by `cask export`
+        tarball: PathBuf,
+    },
+    /// List cached environments in the Holotree
+    List {
+        /// Emit machine-readable JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Summarize every project (cask.yaml / cask.toml) below the current directory,
+    /// or every member of the enclosing workspace
+    Status {
+        /// Emit machine-readable JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Remove environments that haven't been used in a while
+    Prune {
+        /// Remove environments unused for at least this many days
+        #[arg(long)]
+        days: u64,
+
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Installs a uv release and makes it the default engine
+    Upgrade {
+        /// uv version to switch to (defaults to the newest one this cask knows)
+        version: Option<String>,
+    },
+    /// Hardlinks identical files across environments to reclaim disk space
+    Gc {
+        /// Only report what would be reclaimed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Manage the wheel cache shared by all environments
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Print a shell completion script to stdout.
+    ///
+    /// bash:       cask completions bash > ~/.local/share/bash-completion/completions/cask
+    /// zsh:        cask completions zsh > "${fpath[1]}/_cask"
+    /// fish:       cask completions fish > ~/.config/fish/completions/cask.fish
+    /// PowerShell: cask completions powershell >> $PROFILE
+    #[command(verbatim_doc_comment)]
+    Completions {
+        shell: clap_complete::Shell,
+    },
+    /// Destroys environments to reclaim disk space (all of them, unless filtered)
     Clean {
         /// Skip confirmation prompt
         #[arg(short, long)]
         force: bool,
+
+        /// Only environments unused for at least this long, e.g. 30d, 12h or 90m
+        #[arg(long, value_name = "DURATION", value_parser = parse_age)]
+        older_than: Option<u64>,
+
+        /// Only environments built for this project (the `name` in its cask.yaml)
+        #[arg(long)]
+        project: Option<String>,
+
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Delete downloaded wheels; built environments are left untouched
+    Clean,
+}
+
+#[derive(Args, Clone)]
+struct RunArgs {
+    #[arg(short, long, default_value = config::DEFAULT_CONFIG)]
+    config: PathBuf,
+
+    /// Override the Python version from the config (the file on disk is not modified)
+    #[arg(long)]
+    python: Option<String>,
+
+    /// Include dev_dependencies (uses cask.dev.lock instead of cask.lock)
+    #[arg(long)]
+    dev: bool,
+
+    /// Optional dependency groups to include, e.g. --extras ocr,pdf
+    #[arg(long, value_delimiter = ',')]
+    extras: Vec<String>,
+
+    /// Fail instead of relocking when cask.yaml no longer matches the lockfile
+    #[arg(long)]
+    frozen: bool,
+
+    /// Lockfile to enforce and relock into, instead of cask.lock next to the config
+    #[arg(long, value_name = "PATH")]
+    lock: Option<PathBuf>,
+
+    /// Write a JSON summary of the run (hash, cache hit, exit code, duration) to this path
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Extra env file(s) loaded after .env, in order; later files override earlier keys
+    #[arg(long = "env-file", value_name = "PATH")]
+    env_files: Vec<PathBuf>,
+
+    /// Set a variable for this run only, like `docker run -e`; wins over .env and --env-file
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = parse_env_pair)]
+    env: Vec<(String, String)>,
+
+    /// Whether .env values replace variables already set in the shell (the default), or
+    /// only fill in the ones that are missing. Does not affect --env-file or --env.
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    dotenv_override: bool,
+
+    /// Print the payload's complete environment (secrets masked) to stderr before running it
+    #[arg(long)]
+    print_env: bool,
+
+    /// Prefix every line of the payload's output with this label (for parallel runs)
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Also write the payload's output to a timestamped log file in this directory
+    #[arg(long, value_name = "DIR")]
+    capture: Option<PathBuf>,
+
+    /// With --capture, keep only the newest N logs in the directory
+    #[arg(long, value_name = "N", requires = "capture")]
+    keep: Option<usize>,
+
+    /// Stop the payload after this many seconds (SIGTERM, then SIGKILL 10s later) and exit 124
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: Option<u64>,
+
+    /// Re-run the payload whenever a file under the project root changes
+    #[arg(long)]
+    watch: bool,
+
+    /// Resolve and print the plan (identity, build or reuse, command, env files) without
+    /// building or running anything. Still fails on an invalid config.
+    #[arg(long, conflicts_with = "watch")]
+    dry_run: bool,
+
+    /// Run the payload once per Python version, each in its own environment, and
+    /// summarize pass/fail. The lockfile is never rewritten for these runs.
+    #[arg(long, value_name = "VERSIONS", value_delimiter = ',', conflicts_with_all = ["python", "watch", "report"])]
+    each_python: Vec<String>,
+
+    /// With --each-python, run all versions at once (output lines are prefixed with the version)
+    #[arg(long, requires = "each_python")]
+    parallel: bool,
+
+    /// Directory to launch the payload from (relative to where cask is invoked).
+    /// Precedence: --cwd, then the blueprint's `working_dir`, then the directory of a
+    /// config found in a parent directory, else the current directory.
+    /// .env and the config are always resolved next to the config, not here.
+    #[arg(long, value_name = "DIR")]
+    cwd: Option<PathBuf>,
+
+    /// Run a Python script read from stdin; any args become its sys.argv[1:]
+    #[arg(long, conflicts_with_all = ["watch", "each_python"])]
+    stdin: bool,
+
+    /// Set when the config was found in a parent directory
+    #[arg(skip)]
+    workdir: Option<PathBuf>,
+
+    /// A task from the blueprint's `tasks`, or arguments for Python (e.g. "robot.py" or "-m robocorp.tasks ...")
+    /// We allow hyphens so you can pass flags like "-m" or "--verbose" to Python
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
 
-    // 0. Handle Init (No engine needed yet)
-    if let Commands::Init { name } = &cli.command {
-        return init_project(name.clone());
+impl RunArgs {
+    fn env_options(&self) -> EnvOptions {
+        EnvOptions {
+            python: self.python.clone(),
+            dev: self.dev,
+            extras: normalize_extras(&self.extras),
+            frozen: self.frozen,
+            no_build: false,
+            dry_run: self.dry_run,
+            keep_lock: !self.each_python.is_empty(),
+            lock: self.lock.clone(),
+        }
     }
+}
 
-    // 1. Ensure the engine (uv) is present before doing anything else
-    let engine = bootstrap::Engine::ensure()?;
+/// Machine-readable summary written by `cask run --report`
+#[derive(Serialize)]
+struct RunReport {
+    project: Option<String>,
+    env_hash: String,
+    python: String,
+    cached: bool,
+    exit_code: Option<i32>,
+    duration_secs: f64,
+}
 
-    match &cli.command {
-        Commands::Init { .. } => unreachable!(), // Handled above
+fn main() {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Err(e) = interrupt::install_handler() {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(1);
+    }
+
+    // Only a defaulted --config is searched for in parent directories
+    let explicit_config = cli.command.config_mut().is_some()
+        && matches
+            .subcommand()
+            .is_some_and(|(_, sub)| sub.value_source("config") == Some(ValueSource::CommandLine));
 
-        Commands::Clean { force } => {
-            clean_holotree(*force)?;
+    let outcome = run(cli, explicit_config);
+    holotree::discard_ephemeral();
+    telemetry::shutdown();
+    if interrupt::interrupted() {
+        eprintln!("{} Interrupted.", "🛑".red());
+        std::process::exit(interrupt::EXIT_CODE);
+    }
+    if let Err(e) = outcome {
+        eprintln!("Error: {}", secrets::redact(&format!("{:?}", e)));
+        std::process::exit(exit::code_for(&e));
+    }
+}
+
+fn run(mut cli: Cli, explicit_config: bool) -> Result<()> {
+    // Needs nothing from the machine; works even with a broken CASK_HOME
+    if let Commands::Completions { shell } = cli.command {
+        return print_completions(shell);
+    }
+    ui::set_verbose(cli.verbose);
+    ui::set_quiet(cli.quiet);
+    // `which` prints a path for tools to consume; keep its progress off stdout
+    ui::set_status_to_stderr(matches!(cli.command, Commands::Which { .. }));
+    ui::set_color(match cli.color {
+        ColorChoice::Auto => None,
+        ColorChoice::Always => Some(true),
+        ColorChoice::Never => Some(false),
+    });
+    ui::init_logging(cli.log_level.as_deref(), cli.json_logs).classify(Failure::Config)?;
+    jobs::configure(cli.jobs).classify(Failure::Config)?;
+
+    // CLI > environment > $CASK_HOME/config.toml > built-in default
+    let global = config::GlobalConfig::get().classify(Failure::Config)?;
+    bootstrap::set_offline(cli.offline || bootstrap::offline_from_env().or(global.offline).unwrap_or(false));
+    if bootstrap::offline() {
+        status!("{} Offline mode: network access is disabled", "✈️".yellow());
+    }
+
+    environment::set_frozen_lockfile(cli.frozen_lockfile || environment::frozen_lockfile_from_env());
+    bootstrap::set_uv_override(cli.uv_path.clone()).classify(Failure::Engine)?;
+
+    let storage = match cli.storage {
+        Some(storage) => storage,
+        None => holotree::storage_from_env().classify(Failure::Config)?.unwrap_or(holotree::Storage::Persistent),
+    };
+    holotree::set_storage(storage).classify(Failure::Config)?;
+    if storage == holotree::Storage::Ephemeral {
+        status!("{} Ephemeral storage: environments are built from scratch and deleted on exit", "🧪".yellow());
+    }
+
+    let cask_home = holotree::ensure_cask_home().classify(Failure::Config)?;
+    tracing::debug!("{} Cask home: {}", "🏠", cask_home.display());
+    holotree::configure_uv_cache().classify(Failure::Config)?;
+    match holotree::sweep_stale_builds() {
+        Ok(0) => {}
+        Ok(swept) => tracing::debug!("   Removed {} leftover partial build(s)", swept),
+        Err(e) => tracing::warn!("{} Could not clean up partial builds: {:#}", "⚠️".yellow(), e),
+    }
+
+    let discovered = match cli.command.config_mut() {
+        Some(config) if !explicit_config => discover_config(config)?,
+        _ => None,
+    };
+    // No project here, but a workspace: `lock` covers every member, `run` picks one
+    let workspace = match cli.command.config_mut() {
+        Some(config) if !explicit_config && discovered.is_none() && !config.exists() => {
+            workspace::Workspace::find(Path::new(".")).classify(Failure::Config)?
+        }
+        _ => None,
+    };
+    let discovered = match (&workspace, &mut cli.command) {
+        (Some(workspace), Commands::Run(args)) => Some(workspace_member(workspace, args)?),
+        _ => discovered,
+    };
+    if let Some(found) = discovered {
+        status!("{} Using config {}", "📍".cyan(), found.display());
+        if let Commands::Run(args) = &mut cli.command {
+            args.workdir = found.parent().filter(|dir| !dir.as_os_str().is_empty()).map(Path::to_path_buf);
+        }
+        if let Some(config) = cli.command.config_mut() {
+            *config = found;
         }
+    }
 
-        Commands::Lock { config } => {
-            lock_dependencies(&engine.path, config)?;
+    // 0. Handle commands that don't need the engine
+    match &cli.command {
+        Commands::Init { name, template } => return init_project(name.clone(), template),
+        Commands::List { json } => return list_holotree(*json),
+        Commands::Status { json } => return workspace_status(*json),
+        Commands::Info { env: Some(hash), .. } => return show_provenance(hash),
+        Commands::Info { config, .. } => return show_info(config),
+        Commands::Doctor => return run_doctor(),
+        Commands::Requirements { config, dev, extras, locked } => {
+            return print_requirements(config, *dev, &normalize_extras(extras), *locked)
+        }
+        Commands::Prune { days, dry_run } => return prune_holotree(*days, *dry_run),
+        Commands::Gc { dry_run } => return gc_holotree(*dry_run),
+        Commands::Cache { action: CacheAction::Clean } => return clean_cache(),
+        Commands::Import { tarball } => return import_env(tarball),
+        Commands::Upgrade { version } => return upgrade_engine(version.as_deref()).classify(Failure::Engine),
+        // Never download the engine either; without it only the hash can be resolved
+        Commands::Which { config, no_build: true } => {
+            let uv = bootstrap::Engine::locate(requested_engine_version(&cli.command).as_deref())
+                .classify(Failure::Engine)?;
+            return print_interpreter(&uv, config, true);
         }
+        _ => {}
+    }
 
-        Commands::Run { config, args } => {
-            // A. Resolve Project Root (for .env and relative paths)
-            let project_root = config.parent()
-                .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p })
-                .unwrap_or(Path::new("."));
+    // 1. Ensure the engine (uv) is present before doing anything else
+    let engine_version = requested_engine_version(&cli.command);
+    let engine = bootstrap::Engine::ensure(engine_version.as_deref()).classify(Failure::Engine)?;
 
-            // B. Check for Lockfile & Auto-Update if Stale (Drift Detection)
-            let lock_path = config.with_file_name("cask.lock");
-            
-            if config.exists() && lock_path.exists() {
-                let yaml_meta = fs::metadata(config)?;
-                let lock_meta = fs::metadata(&lock_path)?;
+    match &cli.command {
+        Commands::Init { .. }
+        | Commands::List { .. }
+        | Commands::Status { .. }
+        | Commands::Info { .. }
+        | Commands::Doctor
+        | Commands::Requirements { .. }
+        | Commands::Prune { .. }
+        | Commands::Gc { .. }
+        | Commands::Cache { .. }
+        | Commands::Completions { .. }
+        | Commands::Import { .. }
+        | Commands::Upgrade { .. }
+        | Commands::Which { no_build: true, .. } => unreachable!(), // Handled above
+
+        Commands::Clean { force, older_than, project, dry_run } => {
+            clean_holotree(*force, *older_than, project.as_deref(), *dry_run)?;
+        }
 
-                if yaml_meta.modified()? > lock_meta.modified()? {
-                    println!("{} Dependency drift detected (cask.yaml is newer).", "🔄".yellow());
-                    lock_dependencies(&engine.path, config)?;
-                }
+        Commands::Lock { config, python, dev, extras, check, upgrade, output } => {
+            let env_opts = EnvOptions {
+                python: python.clone(),
+                dev: *dev,
+                extras: normalize_extras(extras),
+                lock: output.clone(),
+                ..Default::default()
+            };
+            if let Some(workspace) = &workspace {
+                lock_workspace(workspace, &env_opts, *check, &Upgrade::from_flags(upgrade))?;
+            } else if *check {
+                check_lock(&engine.path, config, &env_opts).classify(Failure::Build)?;
+            } else {
+                let upgrade = Upgrade::from_flags(upgrade);
+                lock_dependencies(&engine.path, config, &env_opts, &upgrade).classify(Failure::Build)?;
             }
+        }
 
-            // C. Determine Effective Configuration (Lock vs YAML)
-            let (_, effective_config) = if lock_path.exists() {
-                println!("{} Found cask.lock. Enforcing Strict Mode.", "🛡️".green());
-                (true, lock_path.as_path())
+        Commands::Run(opts) => {
+            if opts.watch {
+                watch_project(&engine.path, opts)?;
+            } else if !opts.each_python.is_empty() {
+                run_matrix(&engine.path, opts)?;
             } else {
-                println!("{} No lockfile found. Using loose dependencies.", "⚠️".yellow());
-                (false, config.as_path())
-            };
+                run_project(&engine.path, opts)?;
+            }
+        }
+
+        Commands::Shell { config } => {
+            let code = open_shell(&engine.path, config)?;
+            holotree::discard_ephemeral();
+            telemetry::shutdown(); // Exiting directly skips main's flush
+            std::process::exit(code);
+        }
+
+        Commands::Freeze { config, output } => {
+            freeze_env(&engine.path, config, output.as_deref()).classify(Failure::Build)?;
+        }
+
+        Commands::Verify { config } => {
+            verify_env(&engine.path, config)?;
+        }
+
+        Commands::Audit { config, fail_on } => {
+            audit_env(&engine.path, config, *fail_on)?;
+        }
 
-            // D. Load Blueprint (We always need this for Metadata & Python Version)
-            if !config.exists() {
-                anyhow::bail!("Config file not found: {:?}", config);
+        Commands::Export { config, output } => {
+            export_env(&engine.path, config, output.as_deref())?;
+        }
+
+        Commands::Which { config, .. } => {
+            print_interpreter(&engine.path, config, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Generated from the clap definitions, so new commands and flags complete automatically
+fn print_completions(shell: clap_complete::Shell) -> Result<()> {
+    use std::io::Write;
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "cask", &mut script);
+    std::io::stdout().write_all(&script)?;
+    Ok(())
+}
+
+impl Commands {
+    /// The `--config` of commands that operate on a project
+    fn config_mut(&mut self) -> Option<&mut PathBuf> {
+        match self {
+            Commands::Run(RunArgs { config, .. })
+            | Commands::Lock { config, .. }
+            | Commands::Info { config, .. }
+            | Commands::Shell { config }
+            | Commands::Export { config, .. }
+            | Commands::Freeze { config, .. }
+            | Commands::Verify { config }
+            | Commands::Audit { config, .. }
+            | Commands::Requirements { config, .. }
+            | Commands::Which { config, .. } => Some(config),
+            _ => None,
+        }
+    }
+}
+
+// --- CORE LOGIC ---
+
+/// Looks for `name` in the parent directories when it isn't in the CWD, stopping at the
+/// filesystem root or at a repository boundary (a directory containing `.git`).
+/// For the default name each directory is also searched for the other formats (cask.toml).
+fn discover_config(name: &Path) -> Result<Option<PathBuf>> {
+    if name.exists() || name.is_absolute() {
+        return Ok(None);
+    }
+    let names = config::config_names(name);
+    if let Some(local) = names.iter().find(|candidate| candidate.is_file()) {
+        return Ok(Some(local.clone()));
+    }
+    let cwd = std::env::current_dir()?;
+    if cwd.join(".git").exists() {
+        return Ok(None);
+    }
+    for dir in cwd.ancestors().skip(1) {
+        if let Some(candidate) = names.iter().map(|name| dir.join(name)).find(|c| c.is_file()) {
+            return Ok(Some(candidate));
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    Ok(None)
+}
+
+/// `cask run <member> -- ...` at a workspace root: takes the member off the arguments
+/// and returns its config
+fn workspace_member(workspace: &workspace::Workspace, args: &mut RunArgs) -> Result<PathBuf> {
+    if args.args.is_empty() {
+        let members = workspace.members().classify(Failure::Config)?;
+        let names: Vec<String> = members.iter().map(|m| m.path.display().to_string()).collect();
+        bail_as!(Failure::Config, "Name the workspace member to run: cask run <member> -- ... (members: {})", names.join(", "));
+    }
+    let name = args.args.remove(0);
+    if args.args.first().is_some_and(|arg| arg == "--") {
+        args.args.remove(0);
+    }
+    let member = workspace.member(&name).classify(Failure::Config)?;
+    Ok(member.config)
+}
+
+/// `cask lock` at a workspace root: locks (or checks) each member with its own engine,
+/// carrying on past failures so one broken project doesn't hide the state of the rest
+fn lock_workspace(workspace: &workspace::Workspace, opts: &EnvOptions, check: bool, upgrade: &Upgrade) -> Result<()> {
+    if opts.lock.is_some() {
+        bail_as!(Failure::Config, "--output names a single lockfile; it can't be used for a whole workspace");
+    }
+    let members = workspace.members().classify(Failure::Config)?;
+    let mut failed = Vec::new();
+    for member in &members {
+        status!("{} Workspace member {}", "🧩".cyan(), member.path.display());
+        let result = config::Blueprint::load(&member.config).and_then(|blueprint| {
+            let engine = bootstrap::Engine::ensure(blueprint.engine_version.as_deref())?;
+            if check {
+                check_lock(&engine.path, &member.config, opts)
+            } else {
+                lock_dependencies(&engine.path, &member.config, opts, upgrade)
             }
-            let blueprint = config::Blueprint::load(config)?;
-            
-            if let Some(name) = &blueprint.name {
-                println!("🤖 Project: {}", name.cyan().bold());
+        });
+        if let Err(e) = result {
+            if interrupt::interrupted() {
+                return Err(e);
             }
-            if let Some(desc) = &blueprint.description {
-                println!("📄 {}", desc.italic());
+            tracing::error!("{} {}: {:#}", "❌".red(), member.path.display(), e);
+            failed.push(member.path.display().to_string());
+        }
+    }
+    if !failed.is_empty() {
+        bail_as!(Failure::Build, "{} of {} workspace member(s) failed: {}", failed.len(), members.len(), failed.join(", "));
+    }
+    status!("{} {} {} workspace member(s)", "✅".green(), if check { "Checked" } else { "Locked" }, members.len());
+    Ok(())
+}
+
+/// The uv version pinned by the command's config, if any.
+/// Parse errors are ignored here; the command reports them when it loads the config.
+fn requested_engine_version(command: &Commands) -> Option<String> {
+    let config = match command {
+        Commands::Run(RunArgs { config, .. })
+        | Commands::Lock { config, .. }
+        | Commands::Shell { config }
+        | Commands::Export { config, .. }
+        | Commands::Freeze { config, .. }
+        | Commands::Verify { config }
+        | Commands::Audit { config, .. }
+        | Commands::Which { config, .. } => config,
+        _ => return None,
+    };
+    config::Blueprint::load(config).ok()?.engine_version
+}
+
+fn run_project(uv: &Path, opts: &RunArgs) -> Result<()> {
+    let started = Instant::now();
+
+    // Unlike the implicit .env, explicitly requested files must exist (check before building)
+    if let Some(missing) = opts.env_files.iter().find(|f| !f.exists()) {
+        bail_as!(Failure::Config, "Env file not found: {:?}", missing);
+    }
+
+    // Read up front: build steps inherit stdin and must not swallow the script
+    let script = if opts.stdin { Some(read_stdin_script()?) } else { None };
+
+    // A bare `cask run` lists the project's tasks, when it has any
+    if opts.args.is_empty() && !opts.stdin && opts.config.exists() {
+        let blueprint = config::Blueprint::load(&opts.config)?;
+        if !blueprint.tasks.is_empty() {
+            list_tasks(&blueprint);
+            return Ok(());
+        }
+    }
+
+    let env = prepare_env(uv, &opts.config, &opts.env_options())?;
+    let payload = match &script {
+        Some(script) => {
+            let path = script.path().to_string_lossy().to_string();
+            Payload::Python(std::iter::once(path).chain(opts.args.iter().cloned()).collect())
+        }
+        None => Payload::resolve(&env.blueprint, &opts.args)?,
+    };
+
+    // H. Execute Payload
+    let cwd = payload_cwd(opts, &env.blueprint)?;
+    if opts.dry_run {
+        print_plan(&env, cwd.as_deref(), opts, &payload)?;
+        return Ok(());
+    }
+    let run_opts = RunOptions {
+        cwd,
+        env_files: opts.env_files.clone(),
+        prefix: opts.prefix.clone(),
+        capture: opts.capture.clone(),
+        keep: opts.keep,
+        timeout: opts.timeout.map(Duration::from_secs),
+        env: opts.env.clone(),
+        dotenv_keep_existing: !opts.dotenv_override,
+        print_env: opts.print_env,
+    };
+    let outcome = run_task(&env, &payload, &run_opts);
+
+    if let Some(report_path) = &opts.report {
+        let report = RunReport {
+            project: env.blueprint.name.clone(),
+            env_hash: env.env_hash.clone(),
+            python: env.blueprint.python.clone(),
+            cached: env.cached,
+            exit_code: outcome.as_ref().ok().and_then(ExitStatus::code),
+            duration_secs: started.elapsed().as_secs_f64(),
+        };
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write report to {:?}", report_path))?;
+    }
+
+    let status = outcome?;
+    if !status.success() {
+        return Err(exit::PayloadFailed(status.code().unwrap_or(1)).into());
+    }
+    Ok(())
+}
+
+/// Outcome of one interpreter in `cask run --each-python`
+struct MatrixResult {
+    python: String,
+    outcome: Result<()>,
+    duration: Duration,
+}
+
+/// `cask run --each-python`: the payload once per interpreter, sequentially unless
+/// `--parallel`. Every version still gets its own hash and shares the usual build slots.
+fn run_matrix(uv: &Path, opts: &RunArgs) -> Result<()> {
+    let mut versions: Vec<String> = Vec::new();
+    for version in opts.each_python.iter().map(|v| v.trim()).filter(|v| !v.is_empty()) {
+        if !versions.iter().any(|known| known == version) {
+            versions.push(version.to_string());
+        }
+    }
+    if versions.is_empty() {
+        bail_as!(Failure::Config, "--each-python needs at least one version, e.g. --each-python 3.11,3.12");
+    }
+
+    let run_one = |version: &String| {
+        let mut args = opts.clone();
+        args.python = Some(version.clone());
+        if opts.parallel && args.prefix.is_none() {
+            args.prefix = Some(format!("py{}", version));
+        }
+        let started = Instant::now();
+        let outcome = run_project(uv, &args);
+        if let Err(e) = &outcome {
+            tracing::error!("{} Python {}: {:#}", "❌".red(), version, e);
+        }
+        MatrixResult { python: version.clone(), outcome, duration: started.elapsed() }
+    };
+
+    let results: Vec<MatrixResult> = if opts.parallel {
+        std::thread::scope(|scope| {
+            let started = Instant::now();
+            let handles: Vec<_> = versions.iter().map(|v| scope.spawn(|| run_one(v))).collect();
+            // A panicking leg still gets its row, so the table and exit code account for it
+            versions
+                .iter()
+                .zip(handles)
+                .map(|(version, handle)| {
+                    handle.join().unwrap_or_else(|panic| {
+                        let message = panic
+                            .downcast_ref::<&str>()
+                            .map(|m| m.to_string())
+                            .or_else(|| panic.downcast_ref::<String>().cloned())
+                            .unwrap_or_else(|| "unknown cause".to_string());
+                        tracing::error!("{} Python {}: panicked ({})", "❌".red(), version, message);
+                        MatrixResult {
+                            python: version.clone(),
+                            outcome: Err(anyhow::anyhow!("panicked: {}", message)),
+                            duration: started.elapsed(),
+                        }
+                    })
+                })
+                .collect()
+        })
+    } else {
+        let mut results = Vec::new();
+        for version in &versions {
+            results.push(run_one(version));
+            if interrupt::interrupted() {
+                break;
             }
+        }
+        results
+    };
 
-            // E. Resolve Holotree Path
-            let base_dirs = BaseDirs::new().context("No home dir")?;
-            let holotree_root = base_dirs.home_dir().join(".cask").join("holotree");
-            
-            // F. Calculate Identity (Content-Addressable Hash)
-            let env_hash = calculate_hash(effective_config, &blueprint.python)?;
-            let env_path = holotree_root.join(&env_hash);
-
-            println!("{} Identity: {} (Python {})", "🆔".blue(), env_hash, blueprint.python);
-
-            // G. Build (if missing, with Self-Healing)
-            if !env_path.exists() {
-                println!("{} Building Holotree node...", "🔨".yellow());
-                if let Err(e) = build_env(&engine.path, &env_path, effective_config, &blueprint.python) {
-                    eprintln!("{} Build failed. Cleaning up...", "💥".red());
-                    let _ = fs::remove_dir_all(&env_path); // Prevent zombie envs
-                    return Err(e);
-                }
-            } else {
-                println!("{} Using cached environment.", "⚡".green());
+    println!("\n{:<10} {:<24} TIME", "PYTHON", "RESULT");
+    for result in &results {
+        let verdict = match &result.outcome {
+            Ok(()) => "passed".green(),
+            Err(e) => match e.downcast_ref::<exit::PayloadFailed>() {
+                Some(failed) => format!("failed (exit {})", failed.0).red(),
+                None if exit::code_for(e) == exit::TIMEOUT_CODE => "timed out".red(),
+                None => "error (see above)".red(),
+            },
+        };
+        println!("{:<10} {:<24} {:.1}s", result.python, verdict, result.duration.as_secs_f64());
+    }
+
+    let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} Python version(s) failed", failed, versions.len());
+    }
+    status!("{} All {} Python version(s) passed.", "✅".green(), versions.len());
+    Ok(())
+}
+
+/// `cask run --watch`: runs once, then again after every settled edit below the project root.
+/// A changed cask.yaml is picked up by the usual drift detection, which relocks and rebuilds.
+fn watch_project(uv: &Path, opts: &RunArgs) -> Result<()> {
+    let _defer = interrupt::defer(); // Ctrl-C ends the loop instead of killing cask
+
+    let config = fs::canonicalize(&opts.config)
+        .with_context(|| format!("Config file {:?} not found", opts.config))?;
+    let root = config.parent().context("Config file has no parent directory")?.to_path_buf();
+    let cask_home = holotree::cask_home()?;
+    let ignored = vec![fs::canonicalize(&cask_home).unwrap_or(cask_home)];
+    let watcher = watch::ProjectWatcher::new(&root, ignored)?;
+
+    loop {
+        if let Err(e) = run_project(uv, opts) {
+            tracing::error!("{} {:#}", "❌".red(), e);
+        }
+        // Whatever the run itself wrote (lockfiles, robot output) must not trigger another run
+        watcher.discard_pending();
+        if interrupt::interrupted() {
+            break;
+        }
+
+        status!("\n{} Watching {} for changes (Ctrl-C to stop)...", "👀".cyan(), root.display());
+        let Some(changed) = watcher.next_change() else { break };
+        if changed.contains(&config) {
+            status!("{} {} changed, re-resolving the environment.", "🔄".yellow(), opts.config.display());
+        }
+    }
+
+    status!("{} Stopped watching.", "👋".dimmed());
+    Ok(())
+}
+
+/// Opens an interactive shell with the environment activated; returns its exit code
+fn open_shell(uv: &Path, config: &Path) -> Result<i32> {
+    let env = prepare_env(uv, config, &EnvOptions::default())?;
+    let venv_root = holotree::venv_root(&env.env_path);
+    holotree::touch(&env.env_path)?;
+
+    let shell = if cfg!(target_os = "windows") {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+    };
+
+    let mut command = Command::new(&shell);
+    command.env("VIRTUAL_ENV", &venv_root);
+    command.env("VIRTUAL_ENV_PROMPT", env.blueprint.venv_prompt());
+    command.env("PYTHONNOUSERSITE", "1");
+    command.env("PATH", venv_path_var(&env.env_path)?);
+    load_dotenv(&mut command, &env.project_root, true)?;
+    check_required_env(&command, &env.blueprint.required_env)?;
+
+    status!("{} Entering {} (exit to return)\n", "🐚".cyan(), shell);
+    let status = command.status().with_context(|| format!("Failed to launch {}", shell))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// `cask freeze`: writes `uv pip freeze` of the project's environment as a lock. Unlike
+/// `cask lock` this captures what is on disk, including any manual changes.
+fn freeze_env(uv: &Path, config: &Path, output: Option<&Path>) -> Result<()> {
+    if environment::frozen_lockfile() {
+        anyhow::bail!("--frozen-lockfile forbids rewriting a lockfile");
+    }
+    let opts = EnvOptions { dry_run: true, ..Default::default() };
+    let env = prepare_env(uv, config, &opts)?;
+
+    let env_path = if env.cached {
+        env.env_path.clone()
+    } else {
+        // The config moved on since the last build; the newest env of this project is the best guess
+        let previous = holotree::list()?
+            .into_iter()
+            .filter(|e| e.project.is_some() && e.project == env.blueprint.name && holotree::is_complete(&e.path))
+            .max_by_key(|e| e.last_access.unwrap_or(0));
+        let Some(previous) = previous else {
+            anyhow::bail!("Environment {} is not built yet. Run `cask run` first.", env.env_hash);
+        };
+        tracing::warn!(
+            "{} The current config resolves to {}, which is not built; freezing {} instead. \
+             It was built from an older config, so the snapshot may include drift.",
+            "⚠️".yellow(), env.env_hash, previous.hash
+        );
+        previous.path
+    };
+
+    let output = output.map(Path::to_path_buf).unwrap_or_else(|| lock_path_for(config, &opts));
+    status!("{} Freezing {}...", "❄️".cyan(), env_path.display());
+    let freeze = interrupt::output(&mut uv_pip(uv, &env_path, "freeze"))?;
+    if !freeze.status.success() {
+        anyhow::bail!("uv pip freeze failed: {}", String::from_utf8_lossy(&freeze.stderr).trim());
+    }
+    fs::write(&output, &freeze.stdout).with_context(|| format!("Failed to write {:?}", output))?;
+
+    // A snapshot of an older env doesn't reflect the current inputs; let drift fall back to mtime
+    let inputs = if env_path == env.env_path { Some(identity::lock_inputs(&env.blueprint, &opts)?) } else { None };
+    let python = holotree::Metadata::read(&env_path).map_or(env.blueprint.python, |m| m.python);
+    lockfile::write_header(&output, &python, inputs)?;
+
+    let count = String::from_utf8_lossy(&freeze.stdout).lines().filter(|l| !l.trim().is_empty()).count();
+    status!("{} Froze {} package(s) to {}", "✅".green(), count, output.display());
+    Ok(())
+}
+
+/// `cask verify`: diffs `uv pip freeze` of the built environment against the lock's pins
+fn verify_env(uv: &Path, config: &Path) -> Result<()> {
+    let opts = EnvOptions { no_build: true, ..Default::default() };
+    let env = prepare_env(uv, config, &opts)?;
+    let lock_path = lock_path_for(config, &opts);
+    if !lock_path.exists() {
+        anyhow::bail!("{} does not exist, so there is nothing to verify against. Run `cask lock` first.", lock_path.display());
+    }
+
+    status!("{} Verifying {} against {}...", "🔍".cyan(), env.env_hash, lock_path.display());
+    let locked = lockfile::pins(&fs::read_to_string(&lock_path)?);
+    let installed = installed_pins(uv, &env.env_path)?;
+
+    let problems = lockfile::diff(&locked, &installed);
+    for problem in &problems {
+        match problem {
+            Discrepancy::Missing { name, pin } => status!("   {} missing   {}", "-".red(), pin.describe(name)),
+            Discrepancy::Extra { name, pin } => status!("   {} extra     {}", "+".yellow(), pin.describe(name)),
+            Discrepancy::Version { name, locked, installed } => {
+                status!("   {} version   {} (locked {}, installed {})", "~".yellow(), name, locked, installed)
             }
+        }
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("Environment {} differs from {} in {} package(s)", env.env_hash, lock_path.display(), problems.len());
+    }
+    status!("{} All {} locked package(s) are installed as pinned", "✅".green(), locked.len());
+    Ok(())
+}
+
+/// What `uv pip freeze` reports for a built environment
+fn installed_pins(uv: &Path, env_path: &Path) -> Result<std::collections::BTreeMap<String, lockfile::Pin>> {
+    let freeze = interrupt::output(&mut uv_pip(uv, env_path, "freeze"))?;
+    if !freeze.status.success() {
+        anyhow::bail!("uv pip freeze failed: {}", String::from_utf8_lossy(&freeze.stderr).trim());
+    }
+    Ok(lockfile::pins(&String::from_utf8_lossy(&freeze.stdout)))
+}
+
+/// `cask audit`: builds the environment if needed, then reports known advisories for
+/// what is installed. Fails when any reach `fail_on`.
+fn audit_env(uv: &Path, config: &Path, fail_on: audit::Severity) -> Result<()> {
+    let env = prepare_env(uv, config, &EnvOptions::default())?;
+    let installed = installed_pins(uv, &env.env_path)?;
 
-            // H. Execute Payload
-            run_task(&env_path, args, project_root)?;
+    status!("{} Auditing {} package(s) in {} against OSV...", "🔍".cyan(), installed.len(), env.env_hash);
+    let advisories = audit::scan(&installed)?;
+    for advisory in &advisories {
+        let severity = match advisory.severity {
+            Some(audit::Severity::Critical) => "critical".red().bold(),
+            Some(audit::Severity::High) => "high".red(),
+            Some(audit::Severity::Moderate) => "moderate".yellow(),
+            Some(audit::Severity::Low) => "low".normal(),
+            None => "unrated".dimmed(),
+        };
+        status!(
+            "   {} {}=={} {} [{}]",
+            "!".red(), advisory.package, advisory.version, advisory.id, severity
+        );
+        if let Some(summary) = &advisory.summary {
+            status!("      {}", summary);
+        }
+        if !advisory.fixed.is_empty() {
+            status!("      fixed in {}", advisory.fixed.join(", "));
         }
     }
 
+    let failing = advisories.iter().filter(|a| a.at_least(fail_on)).count();
+    if failing > 0 {
+        anyhow::bail!("{} advisory(ies) at or above {} severity in {}", failing, fail_on, env.env_hash);
+    }
+    if advisories.is_empty() {
+        status!("{} No known vulnerabilities", "✅".green());
+    } else {
+        status!("{} {} advisory(ies), all below {} severity", "✅".green(), advisories.len(), fail_on);
+    }
     Ok(())
 }
 
-// --- CORE LOGIC ---
+/// `cask which`: prints the absolute path of the environment's interpreter
+fn print_interpreter(uv: &Path, config: &Path, no_build: bool) -> Result<()> {
+    let opts = EnvOptions { no_build, ..Default::default() };
+    let env = prepare_env(uv, config, &opts)?;
+    let python = holotree::canonical(&env.env_path)?;
+    println!("{}", holotree::python_path(&python).display());
+    Ok(())
+}
+
+fn export_env(uv: &Path, config: &Path, output: Option<&Path>) -> Result<()> {
+    let env = prepare_env(uv, config, &EnvOptions::default())?;
+
+    let output = output.map(Path::to_path_buf).unwrap_or_else(|| {
+        let project = env.blueprint.name.as_deref().unwrap_or("cask-env");
+        PathBuf::from(format!("{}-{}.tar.gz", project, env.env_hash))
+    });
+
+    status!("{} Packing environment...", "📦".magenta());
+    export::export_env(&env.env_path, &env.env_hash, &env.blueprint.python, &output)?;
+    status!("{} Exported {} to {}", "✅".green(), env.env_hash, output.display());
+    Ok(())
+}
 
-fn init_project(name_opt: Option<String>) -> Result<()> {
+fn import_env(tarball: &Path) -> Result<()> {
+    holotree::ensure_writable("import")?;
+    status!("{} Importing {}...", "📦".magenta(), tarball.display());
+    let (manifest, env_path) = export::import_env(tarball)?;
+    status!(
+        "{} Imported {} (Python {}, {}) to {}",
+        "✅".green(), manifest.hash, manifest.python, manifest.platform, env_path.display()
+    );
+    Ok(())
+}
+
+fn init_project(name_opt: Option<String>, template: &str) -> Result<()> {
     let cwd = std::env::current_dir()?;
     let config_path = cwd.join("cask.yaml");
 
     if config_path.exists() {
         anyhow::bail!("cask.yaml already exists in this directory.");
     }
+    let template = templates::Template::parse(template).classify(Failure::Config)?;
 
     let name = name_opt.or_else(|| {
         cwd.file_name()
@@ -159,168 +1123,705 @@ fn init_project(name_opt: Option<String>) -> Result<()> {
            .map(|s| s.to_string())
     }).unwrap_or_else(|| "my-robot".to_string());
 
-    let template = format!(
-r#"name: "{}"
-description: "New automation project"
-python: "3.11"
+    match &template {
+        templates::Template::Builtin(builtin) => {
+            templates::write_builtin(builtin, &cwd, &name)?;
+            status!("{} Initialized new project: {} ({} template)", "✨".green(), name, builtin.name);
+            status!("   Run it with: cask run {}", builtin.run);
+        }
+        custom => {
+            let files = templates::copy_template(custom, &cwd, &name)?;
+            status!("{} Initialized new project: {} ({} file(s) from the template)", "✨".green(), name, files.len());
+            status!("   See its tasks with: cask run");
+        }
+    }
+
+    Ok(())
+}
 
-dependencies:
-  - robocorp-tasks
-  - requests
-"#, name);
+/// `cask requirements`: what goes into the temporary requirements.txt, or the lockfile
+fn print_requirements(config_path: &Path, dev: bool, extras: &[String], locked: bool) -> Result<()> {
+    if !config_path.exists() {
+        bail_as!(Failure::Config, "Config file not found: {:?}", config_path);
+    }
+    let blueprint = config::Blueprint::load(config_path)
+        .with_context(|| format!("Failed to parse {:?}", config_path))
+        .classify(Failure::Config)?;
 
-    fs::write(&config_path, template)?;
-    
-    let task_path = cwd.join("robot.py");
-    if !task_path.exists() {
-        let robot_code = r#"from robocorp.tasks import task
-import os
+    let content = if locked {
+        let opts = EnvOptions { dev, extras: extras.to_vec(), ..Default::default() };
+        let lock_path = lock_path_for(config_path, &opts);
+        if !lock_path.exists() {
+            anyhow::bail!("{} does not exist. Run `cask lock` first.", lock_path.display());
+        }
+        fs::read_to_string(&lock_path).with_context(|| format!("Failed to read {}", lock_path.display()))?
+    } else {
+        blueprint.to_requirements_txt(dev, extras).classify(Failure::Config)?
+    };
+    print!("{}", content);
+    if !content.is_empty() && !content.ends_with('\n') {
+        println!();
+    }
+    Ok(())
+}
 
-@task
-def my_task():
-    print(f"Hello from Cask! API_KEY present: {'API_KEY' in os.environ}")
-"#;
-        fs::write(&task_path, robot_code)?;
+fn show_info(config_path: &Path) -> Result<()> {
+    if !config_path.exists() {
+        bail_as!(Failure::Config, "Config file not found: {:?}", config_path);
     }
+    let mut blueprint = config::Blueprint::load(config_path)
+        .with_context(|| format!("Failed to parse {:?}", config_path))?;
+
+    let python_display = if blueprint.python_is_range() {
+        // Info never downloads, so only resolve against interpreters already present
+        let spec = blueprint.python.clone();
+        let uv = bootstrap::Engine::locate(blueprint.engine_version.as_deref())?;
+        match resolve_python(&uv, &blueprint, false) {
+            Ok(version) => {
+                blueprint.python = version;
+                format!("{} (resolved from {})", blueprint.python, spec)
+            }
+            Err(_) => format!("{} (no matching interpreter installed yet)", spec),
+        }
+    } else {
+        blueprint.python.clone()
+    };
 
-    println!("{} Initialized new project: {}", "✨".green(), name);
-    println!("   Run it with: cask run -- -m robocorp.tasks run robot.py");
+    let lock_path = lock_path_for(config_path, &EnvOptions::default());
+    let lock_state = if !lock_path.exists() {
+        "missing".yellow()
+    } else {
+        match lock_drift(config_path, &lock_path, &EnvOptions::default())? {
+            Some(reason) => format!("stale: {}", reason).yellow(),
+            None => "current".green(),
+        }
+    };
+
+    let effective_config = if lock_path.exists() { lock_path.as_path() } else { config_path };
+    let env_hash = identity::calculate_hash(effective_config, &blueprint, &EnvOptions::default())?;
+    let env_path = holotree::root()?.join(&env_hash);
+    let env_state = if holotree::is_complete(&env_path) {
+        "built".green()
+    } else if env_path.exists() {
+        "incomplete (will rebuild)".yellow()
+    } else {
+        "not built".yellow()
+    };
+
+    println!("{} Config:      {}", "📄".cyan(), config_path.display());
+    if blueprint.extends.is_some() {
+        let chain: Vec<String> = blueprint.inherited_from.iter().map(|p| p.display().to_string()).collect();
+        println!("   Extends:     {}", chain.join(" -> "));
+    }
+    println!("{} Project:     {}", "🤖".cyan(), blueprint.name.as_deref().unwrap_or("-"));
+    if let Some(desc) = &blueprint.description {
+        println!("   Description: {}", desc.italic());
+    }
+    let source = |field: &str| match blueprint.defaulted.get(field) {
+        Some(source) => format!(" ({})", source).dimmed().to_string(),
+        None => String::new(),
+    };
+    println!("{} Python:      {}{}", "🐍".magenta(), python_display, source("python"));
+    let indexes = blueprint.redacted_indexes();
+    if indexes.is_empty() {
+        println!("{} Index:       PyPI{}", "🌐".blue(), source("index_url"));
+    } else {
+        println!("{} Index:       {}{}", "🌐".blue(), indexes.join(", "), source("index_url"));
+    }
+    let global_offline = config::GlobalConfig::get()?.offline;
+    let offline_source = if bootstrap::offline() && bootstrap::offline_from_env().or(global_offline) != Some(true) {
+        "--offline"
+    } else if bootstrap::offline_from_env().is_some() {
+        "CASK_OFFLINE"
+    } else if global_offline.is_some() {
+        "global config"
+    } else {
+        "built-in default"
+    };
+    let offline = if bootstrap::offline() { "yes" } else { "no" };
+    println!("{} Offline:     {} {}", "✈️".cyan(), offline, format!("({})", offline_source).dimmed());
+    println!("{} Lockfile:    {} ({})", "🔒".cyan(), lock_path.display(), lock_state);
+    println!("{} Identity:    {}", "🆔".blue(), env_hash);
+    println!("{} Holotree:    {} ({})", "📦".magenta(), env_path.display(), env_state);
+    let cache_dir = holotree::uv_cache_dir()?;
+    println!("{} Wheel cache: {} ({})", "🗄️".cyan(), cache_dir.display(), holotree::format_bytes(holotree::dir_size(&cache_dir)));
 
     Ok(())
 }
 
-fn calculate_hash(file_path: &Path, python_version: &str) -> Result<String> {
-    let content = fs::read(file_path).with_context(|| format!("Failed to read {:?}", file_path))?;
-    
-    let mut hasher = Sha256::new();
-    hasher.update(python_version.as_bytes());
-    hasher.update(&content);
-    hasher.update(std::env::consts::OS.as_bytes()); // Mix in OS to prevent sharing binary envs
-    
-    let result = hasher.finalize();
-    Ok(hex::encode(result)[..16].to_string())
+/// `cask info --env <hash>`: prints how an environment was built
+fn show_provenance(hash: &str) -> Result<()> {
+    let env_path = holotree::root()?.join(hash);
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) || !env_path.is_dir() {
+        anyhow::bail!("No environment '{}' in the Holotree (see `cask list`)", hash);
+    }
+    let provenance = holotree::Provenance::read(&env_path)
+        .with_context(|| format!("Environment {} has no provenance record (built by an older cask)", hash))?;
+
+    println!("{} Identity:    {}", "🆔".blue(), provenance.env_hash);
+    println!("{} Config hash: sha256:{}", "📄".cyan(), provenance.config_sha256);
+    println!("{} Engine:      {}", "🔧".cyan(), provenance.uv_version);
+    println!("{} Python:      {}", "🐍".magenta(), provenance.python);
+    println!("{} Platform:    {}/{}", "💻".cyan(), provenance.os, provenance.arch);
+    println!("{} Host:        {}", "🏷️".cyan(), provenance.host.as_deref().unwrap_or("-"));
+    println!("{} Built at:    {}", "🕒".cyan(), provenance.built_at);
+    Ok(())
 }
 
-fn lock_dependencies(uv: &Path, config_path: &Path) -> Result<()> {
-    println!("{} Locking dependencies...", "🔒".cyan());
+enum Check {
+    Pass,
+    Warn,
+    Fail,
+}
 
-    let blueprint = config::Blueprint::load(config_path)?;
-    let temp_reqs = config_path.with_extension("tmp");
-    fs::write(&temp_reqs, blueprint.to_requirements_txt())?;
+fn report_check(check: Check, label: &str, detail: &str) {
+    let icon = match check {
+        Check::Pass => "✅".green(),
+        Check::Warn => "⚠️".yellow(),
+        Check::Fail => "❌".red(),
+    };
+    println!("{} {:<12} {}", icon, label, detail);
+}
 
-    let lock_file = config_path.with_file_name("cask.lock");
+fn run_doctor() -> Result<()> {
+    println!("{} Cask doctor\n", "🩺".cyan());
+    let mut critical_failures = 0;
 
-    let status = Command::new(uv)
-        .arg("pip")
-        .arg("compile")
-        .arg(&temp_reqs)
-        .arg("-o")
-        .arg(&lock_file)
-        .arg("--python")
-        .arg(&blueprint.python)
-        .status()?;
+    // Platform
+    match bootstrap::platform_triple() {
+        Ok(triple) => report_check(Check::Pass, "Platform", &triple),
+        Err(e) => {
+            report_check(Check::Fail, "Platform", &e.to_string());
+            critical_failures += 1;
+        }
+    }
 
-    let _ = fs::remove_file(temp_reqs);
+    // Engine
+    let uv_path = bootstrap::Engine::locate(None)?;
+    let engine_present = uv_path.exists();
+    if engine_present {
+        match engine_version(&uv_path) {
+            Ok(version) => {
+                report_check(Check::Pass, "Engine", &format!("{} ({})", version, uv_path.display()));
+            }
+            Err(_) => {
+                report_check(Check::Fail, "Engine", &format!("{} exists but does not run", uv_path.display()));
+                critical_failures += 1;
+            }
+        }
+    } else {
+        report_check(Check::Warn, "Engine", &format!("not installed yet ({})", uv_path.display()));
+    }
 
-    if !status.success() {
-        anyhow::bail!("Failed to lock dependencies");
+    // Network (only critical when we still need to download the engine)
+    if bootstrap::offline() {
+        report_check(Check::Warn, "Network", "skipped (offline mode)");
+    } else {
+        match bootstrap::check_reachability() {
+            Ok(()) => report_check(Check::Pass, "Network", "uv release host reachable"),
+            Err(e) if engine_present => report_check(Check::Warn, "Network", &e.to_string()),
+            Err(e) => {
+                report_check(Check::Fail, "Network", &format!("{} (needed to bootstrap uv)", e));
+                critical_failures += 1;
+            }
+        }
+    }
+
+    // Disk
+    let cask_root = holotree::cask_home()?;
+    let probe = cask_root.ancestors().find(|p| p.exists()).unwrap_or(Path::new("/"));
+    match fs2::available_space(probe) {
+        Ok(bytes) => {
+            let check = if bytes < 1024 * 1024 * 1024 { Check::Warn } else { Check::Pass };
+            report_check(check, "Disk", &format!("{} free under {}", holotree::format_bytes(bytes), probe.display()));
+        }
+        Err(e) => report_check(Check::Warn, "Disk", &format!("could not determine free space: {}", e)),
     }
 
-    println!("{} Locked to {:?}", "✅".green(), lock_file);
+    // Project
+    let names = config::config_names(Path::new(config::DEFAULT_CONFIG));
+    if let Some(config_path) = names.iter().find(|name| name.exists()) {
+        let name = config_path.display();
+        match config::Blueprint::load(config_path) {
+            Ok(bp) => report_check(
+                Check::Pass,
+                "Config",
+                &format!("{} parses ({}, Python {})", name, bp.name.as_deref().unwrap_or("unnamed"), bp.python),
+            ),
+            Err(e) => {
+                report_check(Check::Fail, "Config", &format!("{} is invalid: {}", name, e));
+                critical_failures += 1;
+            }
+        }
+    } else {
+        report_check(Check::Warn, "Config", "no cask.yaml or cask.toml in the current directory");
+    }
+
+    println!();
+    if critical_failures > 0 {
+        anyhow::bail!("{} critical check(s) failed", critical_failures);
+    }
+    println!("{} All critical checks passed.", "✨".green());
     Ok(())
 }
 
-fn build_env(uv: &Path, env_path: &Path, req_file: &Path, python_version: &str) -> Result<()> {
-    fs::create_dir_all(env_path)?;
-
-    // A. Create Venv
-    println!("{} Fetching Python {}...", "🐍".magenta(), python_version);
-    let status = Command::new(uv)
-        .arg("venv")
-        .arg(".venv")
-        .arg("--python")
-        .arg(python_version)
-        .current_dir(env_path)
-        .status()?;
-    
-    if !status.success() { anyhow::bail!("Failed to create venv"); }
-
-    // B. Install Dependencies
-    println!("{} Installing dependencies...", "📦".magenta());
-    
-    let is_yaml = req_file.extension().and_then(|s| s.to_str()) == Some("yaml");
-    
-    let install_target = if is_yaml {
-        // Convert YAML -> temp requirements.txt
-        let bp = config::Blueprint::load(req_file)?;
-        let temp_req = env_path.join("temp_reqs.txt");
-        fs::write(&temp_req, bp.to_requirements_txt())?;
-        temp_req
+/// `cask upgrade`: installs the target uv next to the current one, then switches the default.
+/// The previous engine stays in its own directory, so `cask upgrade <old>` rolls back.
+fn upgrade_engine(version: Option<&str>) -> Result<()> {
+    if let Some(path) = bootstrap::uv_override() {
+        anyhow::bail!("Engine is user-managed ({}, from --uv-path / CASK_UV_PATH); upgrade it with its package manager", path.display());
+    }
+    let target = version.unwrap_or(bootstrap::UV_VERSION);
+    bootstrap::validate_version(target)?;
+
+    let current = bootstrap::default_version();
+    let current_path = bootstrap::Engine::locate(Some(&current))?;
+    let old = current_path.exists().then(|| engine_version(&current_path).ok()).flatten();
+    if current == target && old.is_some() {
+        status!("{} uv {} is already the default engine", "✅".green(), target);
+        return Ok(());
+    }
+
+    let engine = bootstrap::Engine::ensure(Some(target))?;
+    let new = engine_version(&engine.path)
+        .with_context(|| format!("uv {} was installed but does not run; default engine unchanged", target))?;
+    bootstrap::set_default_version(target)?;
+
+    status!(
+        "{} Engine upgraded: {} -> {}",
+        "⬆️".green(),
+        old.as_deref().unwrap_or("(none)"),
+        new.bold()
+    );
+    if old.is_some() {
+        status!("   Roll back with: cask upgrade {}", current);
+    }
+    Ok(())
+}
+
+/// `cask run --dry-run`: what `run_task` would do, without doing it
+fn print_plan(env: &PreparedEnv, cwd: Option<&Path>, opts: &RunArgs, payload: &Payload) -> Result<()> {
+    let command = payload_command(&env.env_path, payload)?;
+
+    let dotenv: Vec<PathBuf> = std::iter::once(".env")
+        .chain(secrets::ENCRYPTED_ENV_FILES.iter().copied())
+        .map(|name| env.project_root.join(name))
+        .filter(|path| path.exists())
+        .collect();
+    let env_files: Vec<String> = dotenv
+        .iter()
+        .chain(&opts.env_files)
+        .map(|path| path.display().to_string())
+        .collect();
+
+    let action = if env.cached { "reuse cached environment".green() } else { "build environment".yellow() };
+    println!();
+    println!("{} Dry run: nothing was built or executed", "📝".cyan());
+    println!("   Identity:    {} ({})", env.env_hash, action);
+    println!("   Holotree:    {}", env.env_path.display());
+    println!("   Python:      {}", holotree::python_path(&env.env_path).display());
+    println!("   Command:     {}", ui::command_line(&command));
+    match cwd {
+        Some(dir) => println!("   Working dir: {}", dir.display()),
+        None => println!("   Working dir: {}", std::env::current_dir()?.display()),
+    }
+    if env_files.is_empty() {
+        println!("   Env files:   (none)");
     } else {
-        // Lockfile: Must use absolute path because we change CWD
-        fs::canonicalize(req_file)?
+        println!("   Env files:   {}", env_files.join(", "));
+    }
+    if !opts.env.is_empty() {
+        let keys: Vec<&str> = opts.env.iter().map(|(key, _)| key.as_str()).collect();
+        println!("   Overrides:   {}", keys.join(", "));
+    }
+    Ok(())
+}
+
+/// `cask run --stdin`: the piped script, spooled to a temp file that is deleted when
+/// the handle drops, however the run ends
+fn read_stdin_script() -> Result<tempfile::NamedTempFile> {
+    use std::io::{IsTerminal, Read, Write};
+    if std::io::stdin().is_terminal() {
+        status!("{} Reading a script from stdin (end it with Ctrl-D)...", "⌨️".cyan());
+    }
+    let mut script = Vec::new();
+    std::io::stdin().read_to_end(&mut script).context("Failed to read the script from stdin")?;
+    let mut file = tempfile::Builder::new().prefix("cask-stdin-").suffix(".py").tempfile()?;
+    file.write_all(&script)?;
+    file.flush()?;
+    Ok(file)
+}
+
+/// `--env KEY=VALUE`; the value may be empty or contain further `=`
+fn parse_env_pair(entry: &str) -> Result<(String, String), String> {
+    let (key, value) = entry.split_once('=').ok_or_else(|| format!("expected KEY=VALUE, got '{}'", entry))?;
+    if key.is_empty() || key.contains('\0') {
+        return Err(format!("invalid variable name in '{}'", entry));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// `--older-than 30d`: a whole number of days, hours, minutes or seconds, returned in seconds
+fn parse_age(spec: &str) -> Result<u64, String> {
+    let split = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+    let (number, unit) = spec.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("expected a duration like 30d, got '{}'", spec))?;
+    let scale = match unit {
+        "d" => 86400,
+        "h" => 3600,
+        "m" => 60,
+        "s" | "" => 1,
+        _ => return Err(format!("unknown unit '{}' in '{}' (use d, h, m or s)", unit, spec)),
     };
+    number.checked_mul(scale).ok_or_else(|| format!("duration too large: '{}'", spec))
+}
 
-    let status = Command::new(uv)
-        .args(["pip", "install", "-r"])
-        .arg(&install_target)
-        .current_dir(env_path)
-        .status()?;
+fn list_tasks(blueprint: &config::Blueprint) {
+    let mut tasks: Vec<_> = blueprint.tasks.iter().collect();
+    tasks.sort();
+    let width = tasks.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
 
-    if is_yaml {
-        let _ = fs::remove_file(&install_target);
+    println!("{} Available tasks (cask run <task>):", "📋".cyan());
+    for (name, command) in tasks {
+        println!("   {:<width$}  {}", name.bold(), command.dimmed(), width = width);
     }
+}
 
-    if !status.success() { anyhow::bail!("Failed to install dependencies"); }
+/// Where the payload starts; see `RunArgs::cwd` for the precedence. None keeps cask's CWD.
+fn payload_cwd(opts: &RunArgs, blueprint: &config::Blueprint) -> Result<Option<PathBuf>> {
+    let dir = opts.cwd.clone()
+        .or_else(|| blueprint.working_dir.clone())
+        .or_else(|| opts.workdir.clone());
+    if let Some(dir) = &dir {
+        if !dir.is_dir() {
+            bail_as!(Failure::Config, "Working directory {} does not exist", dir.display());
+        }
+    }
+    Ok(dir)
+}
+
+fn list_holotree(json: bool) -> Result<()> {
+    let envs = holotree::list()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&envs)?);
+        return Ok(());
+    }
+
+    if envs.is_empty() {
+        println!("{} Holotree is empty.", "✨".green());
+        return Ok(());
+    }
+
+    println!("{:<18} {:<24} {:<8} {:>10}  LAST USED", "HASH", "PROJECT", "PYTHON", "SIZE");
+    for env in &envs {
+        println!(
+            "{:<18} {:<24} {:<8} {:>10}  {}",
+            env.hash.cyan(),
+            env.project.as_deref().unwrap_or("-"),
+            env.python.as_deref().unwrap_or("?"),
+            holotree::format_bytes(env.size_bytes),
+            env.last_access.map(holotree::format_age).unwrap_or_else(|| "-".to_string()),
+        );
+    }
 
+    let total: u64 = envs.iter().map(|e| e.size_bytes).sum();
+    println!("\n{} {} environment(s), {} total", "📦".magenta(), envs.len(), holotree::format_bytes(total));
     Ok(())
 }
 
-fn run_task(env_path: &Path, args: &[String], project_root: &Path) -> Result<()> {
-    let venv_root = env_path.join(".venv");
-    
-    #[cfg(target_os = "windows")]
-    let python = venv_root.join("Scripts").join("python.exe");
-    #[cfg(not(target_os = "windows"))]
-    let python = venv_root.join("bin").join("python");
+/// One project as reported by `cask status`
+#[derive(Serialize)]
+struct ProjectStatus {
+    config: PathBuf,
+    project: Option<String>,
+    python: Option<String>,
+    /// "current", "stale", "missing" or "invalid"
+    lock: &'static str,
+    /// "built", "incomplete", "not built" or "unknown"
+    env: &'static str,
+    env_hash: Option<String>,
+    /// Unix timestamp (seconds) of the last `cask run`
+    last_run: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
 
-    let display_cmd = args.join(" ");
-    println!("{} Launching payload: '{}' \n", "🚀".red(), display_cmd);
+/// `cask status`: lock and environment state of every project in the workspace.
+/// Read-only: nothing is locked, built or downloaded.
+fn workspace_status(json: bool) -> Result<()> {
+    let mut configs = Vec::new();
+    match workspace::Workspace::find(Path::new("."))? {
+        Some(workspace) => {
+            let cwd = fs::canonicalize(".")?;
+            for member in workspace.members()? {
+                let config = member.config.strip_prefix(&cwd).map(Path::to_path_buf).unwrap_or(member.config);
+                configs.push(config);
+            }
+        }
+        None => find_configs(Path::new("."), &mut configs)?,
+    }
+    configs.sort();
+    let rows: Vec<ProjectStatus> = configs.iter().map(|config| project_status(config)).collect();
 
-    let mut command = Command::new(python);
-    command.args(args);
-    command.env("VIRTUAL_ENV", &venv_root);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    if rows.is_empty() {
+        println!("{} No cask projects below the current directory.", "✨".green());
+        return Ok(());
+    }
 
-    // .ENV Injection
-    let dotenv_path = project_root.join(".env");
-    if dotenv_path.exists() {
-        println!("{} Loading secrets from .env", "🔑".yellow());
-        for item in dotenvy::from_path_iter(&dotenv_path)? {
-            let (key, val) = item?;
-            command.env(key, val);
+    println!("{:<24} {:<8} {:<9} {:<11} {:<10} CONFIG", "PROJECT", "PYTHON", "LOCK", "ENV", "LAST RUN");
+    for row in &rows {
+        let lock = match row.lock {
+            "current" => row.lock.green(),
+            "missing" => row.lock.normal(),
+            _ => row.lock.yellow(),
+        };
+        let env = match row.env {
+            "built" => row.env.green(),
+            _ => row.env.yellow(),
+        };
+        println!(
+            "{:<24} {:<8} {:<9} {:<11} {:<10} {}",
+            row.project.as_deref().unwrap_or("-"),
+            row.python.as_deref().unwrap_or("?"),
+            lock,
+            env,
+            row.last_run.map(holotree::format_age).unwrap_or_else(|| "-".to_string()),
+            row.config.display(),
+        );
+        if let Some(error) = &row.error {
+            println!("   {} {}", "↳".red(), error);
         }
     }
+    Ok(())
+}
 
-    let status = command.status()?;
+/// Collects project configs below `dir`, skipping hidden directories (.git, .venv)
+fn find_configs(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    let names = config::config_names(Path::new(config::DEFAULT_CONFIG));
+    for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = entry.file_name();
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            let skipped = name.to_string_lossy().starts_with('.') || name == "node_modules" || name == "target";
+            if !skipped {
+                find_configs(&path, found)?;
+            }
+        } else if names.iter().any(|n| n.as_os_str() == name) {
+            found.push(path.strip_prefix(".").map(Path::to_path_buf).unwrap_or(path));
+        }
+    }
+    Ok(())
+}
 
-    if !status.success() {
-        anyhow::bail!("Process exited with error");
+fn project_status(config: &Path) -> ProjectStatus {
+    let mut status = ProjectStatus {
+        config: config.to_path_buf(),
+        project: None,
+        python: None,
+        lock: "invalid",
+        env: "unknown",
+        env_hash: None,
+        last_run: None,
+        error: None,
+    };
+    let mut blueprint = match config::Blueprint::load(config) {
+        Ok(blueprint) => blueprint,
+        Err(e) => {
+            status.error = Some(format!("{:#}", e));
+            return status;
+        }
+    };
+    status.project = blueprint.name.clone();
+
+    let opts = EnvOptions::default();
+    let lock_path = lock_path_for(config, &opts);
+    status.lock = match lock_drift(config, &lock_path, &opts) {
+        _ if !lock_path.exists() => "missing",
+        Ok(None) => "current",
+        Ok(Some(_)) => "stale",
+        Err(_) => "invalid",
+    };
+
+    // Like `cask info`: a range only resolves against interpreters already installed
+    if blueprint.python_is_range() {
+        let resolved = bootstrap::Engine::locate(blueprint.engine_version.as_deref())
+            .and_then(|uv| resolve_python(&uv, &blueprint, false));
+        match resolved {
+            Ok(version) => blueprint.python = version,
+            Err(_) => {
+                status.python = Some(blueprint.python);
+                return status;
+            }
+        }
+    }
+    status.python = Some(blueprint.python.clone());
+
+    let effective_config = if lock_path.exists() { lock_path.as_path() } else { config };
+    let Ok(env_hash) = identity::calculate_hash(effective_config, &blueprint, &opts) else {
+        return status;
+    };
+    let Ok(env_path) = holotree::root().map(|root| root.join(&env_hash)) else {
+        return status;
+    };
+    status.env = if holotree::is_complete(&env_path) {
+        "built"
+    } else if env_path.exists() {
+        "incomplete"
+    } else {
+        "not built"
+    };
+    if env_path.exists() {
+        status.last_run = holotree::last_used(&env_path);
+    }
+    status.env_hash = Some(env_hash);
+    status
+}
+
+fn prune_holotree(days: u64, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        holotree::ensure_writable("prune")?;
+    }
+    let Some(age) = days.checked_mul(86400) else {
+        anyhow::bail!("--days {} is too large", days);
+    };
+    let cutoff = holotree::unix_now().saturating_sub(age);
+    let stale: Vec<_> = holotree::list()?
+        .into_iter()
+        .filter(|env| env.last_access.is_some_and(|t| t < cutoff))
+        .collect();
+
+    if stale.is_empty() {
+        if !dry_run {
+            holotree::prune_aliases()?; // Envs may also have been deleted by hand
+        }
+        status!("{} Nothing unused for {} day(s).", "✨".green(), days);
+        return Ok(());
+    }
+
+    let verb = if dry_run { "Would remove" } else { "Removing" };
+    let mut freed = 0;
+    for env in &stale {
+        status!(
+            "{} {} {} ({}, last used {})",
+            "🗑️".red(),
+            verb,
+            env.path.display(),
+            holotree::format_bytes(env.size_bytes),
+            env.last_access.map(holotree::format_age).unwrap_or_default(),
+        );
+        if !dry_run {
+            fs::remove_dir_all(&env.path)?;
+        }
+        freed += env.size_bytes;
+    }
+    if !dry_run {
+        holotree::prune_aliases()?;
+    }
+
+    let summary = if dry_run { "would be freed" } else { "freed" };
+    status!("{} {} {}.", "✨".green(), holotree::format_bytes(freed), summary);
+    Ok(())
+}
+
+/// `cask gc`: dedupes files shared by finished environments (builds in progress are left alone)
+fn gc_holotree(dry_run: bool) -> Result<()> {
+    if !dry_run {
+        holotree::ensure_writable("deduplicate")?;
+    }
+    let venvs: Vec<PathBuf> = holotree::list()?
+        .into_iter()
+        .filter(|env| holotree::is_complete(&env.path))
+        .map(|env| holotree::venv_root(&env.path))
+        .collect();
+    if venvs.len() < 2 {
+        status!("{} Nothing to deduplicate ({} environment(s)).", "✨".green(), venvs.len());
+        return Ok(());
+    }
+
+    status!("{} Deduplicating {} environments...", "🔗".cyan(), venvs.len());
+    let report = gc::deduplicate(&venvs, dry_run)?;
+
+    let verb = if dry_run { "Would link" } else { "Linked" };
+    status!(
+        "   {} {} duplicate file(s) out of {} scanned",
+        verb, report.files_linked, report.files_scanned
+    );
+    if report.files_skipped > 0 {
+        tracing::warn!(
+            "{} Kept {} duplicate(s) as copies (hardlinking failed)",
+            "⚠️".yellow(),
+            report.files_skipped
+        );
     }
+    let summary = if dry_run { "would be reclaimed" } else { "reclaimed" };
+    status!("{} {} {}.", "✨".green(), holotree::format_bytes(report.bytes_reclaimed), summary);
     Ok(())
 }
 
-fn clean_holotree(force: bool) -> Result<()> {
-    let base_dirs = BaseDirs::new().context("No home dir")?;
-    let holotree_root = base_dirs.home_dir().join(".cask").join("holotree");
+/// `cask cache clean`: wheels are re-downloaded on demand, so no confirmation is needed
+fn clean_cache() -> Result<()> {
+    let cache_dir = holotree::uv_cache_dir()?;
+    if !cache_dir.exists() {
+        status!("{} Wheel cache is already empty.", "✨".green());
+        return Ok(());
+    }
+    let size = holotree::dir_size(&cache_dir);
+    fs::remove_dir_all(&cache_dir).with_context(|| format!("Failed to remove {:?}", cache_dir))?;
+    status!("{} Cleared the wheel cache ({} freed).", "✨".green(), holotree::format_bytes(size));
+    Ok(())
+}
+
+fn clean_holotree(force: bool, older_than: Option<u64>, project: Option<&str>, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        holotree::ensure_writable("clean")?;
+    }
+    let holotree_root = holotree::root()?;
 
     if !holotree_root.exists() {
-        println!("{} Holotree is already empty.", "✨".green());
+        status!("{} Holotree is already empty.", "✨".green());
+        return Ok(());
+    }
+
+    let filtered = older_than.is_some() || project.is_some();
+    let cutoff = older_than.map(|secs| holotree::unix_now().saturating_sub(secs));
+    let doomed: Vec<_> = holotree::list()?
+        .into_iter()
+        .filter(|env| cutoff.is_none_or(|cutoff| env.last_access.is_some_and(|t| t < cutoff)))
+        .filter(|env| project.is_none_or(|name| env.project.as_deref() == Some(name)))
+        .collect();
+
+    if doomed.is_empty() && filtered {
+        status!("{} No environments match.", "✨".green());
+        return Ok(());
+    }
+
+    let verb = if dry_run { "Would remove" } else { "Will remove" };
+    let mut freed = 0;
+    for env in &doomed {
+        status!(
+            "{} {} {} ({}, {}, last used {})",
+            "🗑️".red(),
+            verb,
+            env.hash,
+            env.project.as_deref().unwrap_or("unnamed"),
+            holotree::format_bytes(env.size_bytes),
+            env.last_access.map(holotree::format_age).unwrap_or_else(|| "never".to_string()),
+        );
+        freed += env.size_bytes;
+    }
+    if dry_run {
+        status!("{} {} would be freed.", "✨".green(), holotree::format_bytes(freed));
         return Ok(());
     }
 
     if !force {
-        let count = fs::read_dir(&holotree_root)?.count();
-        println!("{} Warning: This will delete {} environment(s).", "⚠️".yellow(), count);
+        println!("{} Warning: This will delete {} environment(s).", "⚠️".yellow(), doomed.len());
         print!("   Are you sure? [y/N]: ");
         use std::io::Write;
         std::io::stdout().flush()?;
@@ -334,9 +1835,18 @@ fn clean_holotree(force: bool) -> Result<()> {
         }
     }
 
-    println!("{} Destroying Holotree...", "🔥".red());
+    if filtered {
+        for env in &doomed {
+            fs::remove_dir_all(&env.path)?;
+        }
+        holotree::prune_aliases()?;
+        status!("{} {} freed.", "✨".green(), holotree::format_bytes(freed));
+        return Ok(());
+    }
+
+    status!("{} Destroying Holotree...", "🔥".red());
     fs::remove_dir_all(&holotree_root)?;
-    println!("{} System reset complete.", "✨".green());
+    status!("{} System reset complete, {} freed.", "✨".green(), holotree::format_bytes(freed));
 
     Ok(())
 }
\ No newline at end of file
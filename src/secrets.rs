@@ -0,0 +1,114 @@
+//! Secret handling: masking known values in output, and decrypting the encrypted env
+//! files (`.env.sops`, `.env.age`) a project may commit instead of a plaintext `.env`.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::RwLock;
+
+use crate::config;
+
+/// Shorter values (flags like "1" or "true") would mangle unrelated output
+const MIN_SECRET_LEN: usize = 4;
+const MASK: &str = "***";
+
+/// Every value loaded from an env file, kept longest first so overlapping secrets mask fully
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Remembers an env-file value so `redact` will scrub it from now on
+pub fn register(value: &str) {
+    let value = value.trim();
+    if value.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !secrets.iter().any(|known| known == value) {
+        secrets.push(value.to_string());
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// `text` with every registered secret replaced by "***"
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS.read().unwrap_or_else(|e| e.into_inner());
+    secrets
+        .iter()
+        .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), MASK))
+}
+
+/// Encrypted env files looked for next to `.env`, in load order
+pub const ENCRYPTED_ENV_FILES: &[&str] = &[".env.sops", ".env.age"];
+/// An age secret key ("AGE-SECRET-KEY-1...")
+pub const AGE_KEY_VAR: &str = "CASK_AGE_KEY";
+/// Path of an age identity file; wins over `age_key_file` in the global config
+pub const AGE_KEY_FILE_VAR: &str = "CASK_AGE_KEY_FILE";
+
+/// Where the age key comes from. Neither means sops' own key discovery (SOPS_AGE_KEY_FILE,
+/// KMS, PGP) and, for `.env.age`, an error.
+enum AgeKey {
+    Inline(String),
+    File(PathBuf),
+}
+
+fn age_key() -> Result<Option<AgeKey>> {
+    if let Some(key) = std::env::var(AGE_KEY_VAR).ok().filter(|k| !k.trim().is_empty()) {
+        return Ok(Some(AgeKey::Inline(key.trim().to_string())));
+    }
+    if let Some(file) = std::env::var_os(AGE_KEY_FILE_VAR).filter(|f| !f.is_empty()) {
+        return Ok(Some(AgeKey::File(file.into())));
+    }
+    Ok(config::GlobalConfig::get()?.age_key_file.clone().map(AgeKey::File))
+}
+
+/// Decrypts an encrypted env file into memory with the `sops` or `age` CLI; the plaintext
+/// only ever lives in a pipe and this process
+pub fn decrypt_env_file(path: &Path) -> Result<Vec<u8>> {
+    let key = age_key()?;
+    let sops = path.extension().is_some_and(|ext| ext == "sops");
+    let (tool, mut command) = if sops {
+        let mut command = Command::new("sops");
+        command.args(["--decrypt", "--input-type", "dotenv", "--output-type", "dotenv"]).arg(path);
+        match &key {
+            Some(AgeKey::Inline(key)) => command.env("SOPS_AGE_KEY", key),
+            Some(AgeKey::File(file)) => command.env("SOPS_AGE_KEY_FILE", file),
+            None => &mut command,
+        };
+        ("sops", command)
+    } else {
+        let mut command = Command::new("age");
+        command.arg("--decrypt");
+        match &key {
+            Some(AgeKey::Inline(_)) => command.args(["-i", "-"]),
+            Some(AgeKey::File(file)) => command.arg("-i").arg(file),
+            None => anyhow::bail!(
+                "Cannot decrypt {}: no age key (set {} or {}, or age_key_file in the global config)",
+                path.display(), AGE_KEY_VAR, AGE_KEY_FILE_VAR
+            ),
+        };
+        command.arg(path);
+        ("age", command)
+    };
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Cannot decrypt {}: failed to run {} (is it installed?)", path.display(), tool))?;
+    {
+        let mut stdin = child.stdin.take().context("Decryptor stdin was not captured")?;
+        if let (Some(AgeKey::Inline(key)), false) = (&key, sops) {
+            stdin.write_all(key.as_bytes())?;
+            stdin.write_all(b"\n")?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to decrypt {} with {}: {}",
+            path.display(), tool, redact(String::from_utf8_lossy(&output.stderr).trim())
+        );
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,67 @@
+//! Optional OTLP export of the phase spans (`bootstrap`, `lock`, `prepare`, `build`, `run`).
+//! Off unless CASK_OTLP_ENDPOINT is set; then finished spans are batched to that collector
+//! over HTTP on a background thread, and `shutdown` flushes them before cask exits.
+
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::sync::OnceLock;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Base URL of an OTLP/HTTP collector, e.g. `http://otel-collector:4318`
+pub const ENDPOINT_VAR: &str = "CASK_OTLP_ENDPOINT";
+/// OTLP/HTTP serves traces below the collector's base URL
+const TRACES_PATH: &str = "/v1/traces";
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// The layer that exports spans, or None (and no exporter at all) without CASK_OTLP_ENDPOINT
+pub fn layer<S>() -> Result<Option<OpenTelemetryLayer<S, SdkTracer>>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let Some(endpoint) = std::env::var(ENDPOINT_VAR).ok().filter(|e| !e.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let endpoint = endpoint.trim().trim_end_matches('/');
+    let endpoint = if endpoint.ends_with(TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", endpoint, TRACES_PATH)
+    };
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&endpoint)
+        .build()
+        .with_context(|| format!("Invalid {} '{}'", ENDPOINT_VAR, endpoint))?;
+    let resource = Resource::builder()
+        .with_service_name("cask")
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+        .with_attribute(KeyValue::new("host.arch", std::env::consts::ARCH))
+        .with_attribute(KeyValue::new("os.type", std::env::consts::OS))
+        .build();
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+
+    let tracer = provider.tracer("cask");
+    let _ = PROVIDER.set(provider);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+/// Sends whatever spans are still buffered. A collector that is down costs at most the
+/// exporter's timeout and never fails the command.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            tracing::debug!("   Failed to flush telemetry: {}", e);
+        }
+    }
+}
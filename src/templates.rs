@@ -0,0 +1,274 @@
+//! Starters for `cask init`: a few built-in stacks, or any directory, `.tar.gz` URL or git
+//! repository that contains a cask.yaml (or cask.toml).
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::bootstrap;
+
+/// Replaced with the project name in a custom template's config
+const NAME_PLACEHOLDER: &str = "{{name}}";
+/// A custom template must contain one of these at its root
+const TEMPLATE_CONFIGS: &[&str] = &["cask.yaml", "cask.toml"];
+
+/// One built-in starter: a cask.yaml plus a runnable robot.py
+pub struct Builtin {
+    pub name: &'static str,
+    pub summary: &'static str,
+    dependencies: &'static [&'static str],
+    post_install: &'static [&'static str],
+    script: &'static str,
+    /// Arguments for `cask run`, shown after scaffolding
+    pub run: &'static str,
+}
+
+pub const DEFAULT: &str = "robocorp";
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "robocorp",
+        summary: "robocorp-tasks robot",
+        dependencies: &["robocorp-tasks", "requests"],
+        post_install: &[],
+        script: r#"from robocorp.tasks import task
+import os
+
+@task
+def my_task():
+    print(f"Hello from Cask! API_KEY present: {'API_KEY' in os.environ}")
+"#,
+        run: "-- -m robocorp.tasks run robot.py",
+    },
+    Builtin {
+        name: "playwright",
+        summary: "browser automation with Playwright (installs Chromium)",
+        dependencies: &["playwright"],
+        post_install: &["playwright install chromium"],
+        script: r#"from playwright.sync_api import sync_playwright
+
+def main():
+    with sync_playwright() as p:
+        browser = p.chromium.launch()
+        page = browser.new_page()
+        page.goto("https://example.com")
+        print(f"Page title: {page.title()}")
+        browser.close()
+
+if __name__ == "__main__":
+    main()
+"#,
+        run: "robot.py",
+    },
+    Builtin {
+        name: "requests-api",
+        summary: "HTTP API client with requests",
+        dependencies: &["requests"],
+        post_install: &[],
+        script: r#"import os
+import requests
+
+BASE_URL = os.environ.get("API_BASE_URL", "https://httpbin.org")
+
+def main():
+    response = requests.get(f"{BASE_URL}/json", timeout=30)
+    response.raise_for_status()
+    print(f"{response.status_code} from {response.url}")
+    print(response.json())
+
+if __name__ == "__main__":
+    main()
+"#,
+        run: "robot.py",
+    },
+    Builtin {
+        name: "selenium",
+        summary: "browser automation with Selenium (headless Chrome)",
+        dependencies: &["selenium"],
+        post_install: &[],
+        script: r#"from selenium import webdriver
+
+def main():
+    options = webdriver.ChromeOptions()
+    options.add_argument("--headless=new")
+    driver = webdriver.Chrome(options=options)  # Selenium Manager fetches a matching driver
+    try:
+        driver.get("https://example.com")
+        print(f"Page title: {driver.title}")
+    finally:
+        driver.quit()
+
+if __name__ == "__main__":
+    main()
+"#,
+        run: "robot.py",
+    },
+    Builtin {
+        name: "empty",
+        summary: "bare project with a placeholder dependency",
+        dependencies: &["pip"],
+        post_install: &[],
+        script: r#"def main():
+    print("Hello from Cask!")
+
+if __name__ == "__main__":
+    main()
+"#,
+        run: "robot.py",
+    },
+];
+
+/// Where `cask init --template` takes its files from
+pub enum Template {
+    Builtin(&'static Builtin),
+    Dir(PathBuf),
+    /// A `.tar.gz` over https, or a git repository
+    Remote(String),
+}
+
+impl Template {
+    /// A built-in name, a URL, or an existing directory, in that order
+    pub fn parse(spec: &str) -> Result<Self> {
+        if let Some(builtin) = BUILTINS.iter().find(|b| b.name == spec) {
+            return Ok(Template::Builtin(builtin));
+        }
+        if spec.contains("://") || spec.starts_with("git@") {
+            return Ok(Template::Remote(spec.to_string()));
+        }
+        let dir = PathBuf::from(spec);
+        if dir.is_dir() {
+            return Ok(Template::Dir(dir));
+        }
+        let names: Vec<&str> = BUILTINS.iter().map(|b| b.name).collect();
+        anyhow::bail!(
+            "Unknown template '{}': not a built-in ({}), a directory, or a URL",
+            spec, names.join(", ")
+        )
+    }
+}
+
+/// Renders a built-in starter's cask.yaml for project `name`
+pub fn builtin_config(builtin: &Builtin, name: &str) -> String {
+    let mut yaml = format!(
+        "name: \"{}\"\ndescription: \"New automation project: {}\"\npython: \"3.11\"\n\ndependencies:\n",
+        name, builtin.summary
+    );
+    for dep in builtin.dependencies {
+        yaml.push_str(&format!("  - {}\n", dep));
+    }
+    if !builtin.post_install.is_empty() {
+        yaml.push_str("\npost_install:\n");
+        for hook in builtin.post_install {
+            yaml.push_str(&format!("  - {}\n", hook));
+        }
+    }
+    yaml
+}
+
+/// Writes a built-in starter into `dir`; an existing robot.py is left alone
+pub fn write_builtin(builtin: &Builtin, dir: &Path, name: &str) -> Result<()> {
+    fs::write(dir.join("cask.yaml"), builtin_config(builtin, name))?;
+    let script = dir.join("robot.py");
+    if !script.exists() {
+        fs::write(&script, builtin.script)?;
+    }
+    Ok(())
+}
+
+/// Copies a user template into `dir`, fetching it first if it is remote. Fails without
+/// writing anything if a file would be overwritten. Returns the files created.
+pub fn copy_template(template: &Template, dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    let fetched;
+    let source = match template {
+        Template::Builtin(_) => anyhow::bail!("built-in templates are written by write_builtin"),
+        Template::Dir(path) => path.clone(),
+        Template::Remote(url) => {
+            fetched = fetch(url)?;
+            single_root(fetched.path())?
+        }
+    };
+    if !TEMPLATE_CONFIGS.iter().any(|config| source.join(config).is_file()) {
+        anyhow::bail!("Template {} has no {} at its root", source.display(), TEMPLATE_CONFIGS.join(" or "));
+    }
+
+    let mut files = Vec::new();
+    collect_files(&source, Path::new(""), &mut files)?;
+    files.sort();
+    let clashes: Vec<String> = files.iter().filter(|f| dir.join(f).exists()).map(|f| f.display().to_string()).collect();
+    if !clashes.is_empty() {
+        anyhow::bail!("Template would overwrite existing file(s): {}", clashes.join(", "));
+    }
+
+    for file in &files {
+        let target = dir.join(file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if TEMPLATE_CONFIGS.iter().any(|config| Path::new(config) == file) {
+            let config = fs::read_to_string(source.join(file))?;
+            fs::write(&target, config.replace(NAME_PLACEHOLDER, name))?;
+        } else {
+            fs::copy(source.join(file), &target).with_context(|| format!("Failed to copy {}", file.display()))?;
+        }
+    }
+    Ok(files)
+}
+
+/// Downloads a remote template into a temporary directory
+fn fetch(url: &str) -> Result<tempfile::TempDir> {
+    if bootstrap::offline() {
+        anyhow::bail!("Fetching template {} needs the network, but offline mode is on", url);
+    }
+    let dest = tempfile::tempdir()?;
+    let git_url = url.strip_prefix("git+").unwrap_or(url);
+    if url.starts_with("git+") || url.starts_with("git@") || git_url.trim_end_matches('/').ends_with(".git") {
+        let status = Command::new("git")
+            .args(["clone", "--depth", "1", "--quiet", git_url])
+            .arg(dest.path().join("template"))
+            .status()
+            .context("Failed to run git (is it installed?)")?;
+        if !status.success() {
+            anyhow::bail!("git clone {} failed", git_url);
+        }
+        fs::remove_dir_all(dest.path().join("template").join(".git"))?;
+    } else if url.ends_with(".tar.gz") || url.ends_with(".tgz") {
+        let response = bootstrap::http_client()?
+            .get(url)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Failed to download template {}", url))?;
+        tar::Archive::new(flate2::read::GzDecoder::new(response))
+            .unpack(dest.path())
+            .with_context(|| format!("Failed to unpack template {}", url))?;
+    } else {
+        anyhow::bail!("Template URL {} is neither a git repository nor a .tar.gz archive", url);
+    }
+    Ok(dest)
+}
+
+/// Archives (GitHub's included) usually wrap everything in one top-level directory
+fn single_root(dir: &Path) -> Result<PathBuf> {
+    let entries: Vec<PathBuf> = fs::read_dir(dir)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    match entries.as_slice() {
+        [only] if only.is_dir() => Ok(only.clone()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+/// Every file below `root`, relative to it; VCS metadata is skipped
+fn collect_files(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
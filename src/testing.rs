@@ -0,0 +1,95 @@
+//! Fixtures shared by the unit tests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Held by every test that points CASK_HOME somewhere, or otherwise touches process-wide state
+static PROCESS: Mutex<()> = Mutex::new(());
+
+/// A scratch directory that is CASK_HOME (`<sandbox>/home`) for as long as the test holds it
+pub struct Sandbox {
+    dir: tempfile::TempDir,
+    /// Set through `set_env`, unset again on drop
+    vars: Vec<&'static str>,
+    _turn: MutexGuard<'static, ()>,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Self::named("cask-test-")
+    }
+
+    /// Like `new`, in a directory whose name starts with `prefix`
+    pub fn named(prefix: &str) -> Self {
+        // A failed test poisons the lock; the next one still gets a fresh sandbox
+        let turn = PROCESS.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::Builder::new().prefix(prefix).tempdir().expect("create sandbox");
+        std::env::set_var("CASK_HOME", dir.path().join("home"));
+        Sandbox { dir, vars: Vec::new(), _turn: turn }
+    }
+
+    /// Sets an environment variable until the sandbox is dropped
+    pub fn set_env(&mut self, key: &'static str, value: &str) {
+        std::env::set_var(key, value);
+        self.vars.push(key);
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Writes `file` (relative to the sandbox, parents created) and returns its path
+    pub fn write(&self, file: &str, content: &str) -> PathBuf {
+        let path = self.path().join(file);
+        fs::create_dir_all(path.parent().expect("file in a directory")).expect("create parent");
+        fs::write(&path, content).expect("write fixture");
+        path
+    }
+
+    /// A uv stand-in that needs no network and appends each invocation's arguments to
+    /// `uv.log`. Its venvs hold a bin/python that only answers `--version`; `pip install`
+    /// takes `install_secs`.
+    #[cfg(unix)]
+    pub fn fake_uv(&self, install_secs: u32) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = format!(
+            r#"#!/bin/sh
+echo "$*" >> '{log}'
+case "$1" in
+  --version) echo "uv 0.9.28 (test)" ;;
+  python) exit 1 ;;
+  venv)
+    mkdir -p "$2/bin"
+    printf '#!/bin/sh\nexit 0\n' > "$2/bin/python"
+    chmod +x "$2/bin/python"
+    echo "home = /usr" > "$2/pyvenv.cfg" ;;
+  pip)
+    case "$2" in
+      install) sleep {install_secs} ;;
+      freeze) echo "requests==2.31.0" ;;
+    esac ;;
+esac
+"#,
+            log = self.path().join("uv.log").display(),
+        );
+        let uv = self.write("bin/uv", &script);
+        fs::set_permissions(&uv, fs::Permissions::from_mode(0o755)).expect("make uv executable");
+        uv
+    }
+
+    /// Invocations of `fake_uv` so far, one line each
+    pub fn uv_calls(&self) -> Vec<String> {
+        let log = fs::read_to_string(self.path().join("uv.log")).unwrap_or_default();
+        log.lines().map(str::to_string).collect()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        for key in &self.vars {
+            std::env::remove_var(key);
+        }
+    }
+}
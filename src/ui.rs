@@ -0,0 +1,210 @@
+use anyhow::Result;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use colored::*;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+use crate::{secrets, telemetry};
+
+/// Set once from the global `--verbose` flag
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// Whether to show raw tool output (uv) instead of summarizing it
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Set once from the global `--quiet` flag
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether to drop everything but errors: no status lines, spinners or tool chatter.
+/// The payload's own output is never affected.
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Cleared by `set_color` when status lines should be plain text: no ANSI colors, no emoji
+static DECORATED: AtomicBool = AtomicBool::new(true);
+
+/// `--color`: `Some` forces colors on or off; `None` (auto) enables them only while status
+/// output goes to a terminal and NO_COLOR is unset. Call after `set_status_to_stderr`.
+pub fn set_color(forced: Option<bool>) {
+    let enabled = forced.unwrap_or_else(|| {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let terminal = if status_to_stderr() { io::stderr().is_terminal() } else { io::stdout().is_terminal() };
+        terminal && !no_color
+    });
+    colored::control::set_override(enabled);
+    DECORATED.store(enabled, Ordering::Relaxed);
+}
+
+/// "🔒 Locking..." -> "Locking...": status lines lead with one emoji when they have one
+fn strip_emoji(line: &str) -> &str {
+    let indent = line.len() - line.trim_start().len();
+    match line[indent..].split_once(' ') {
+        Some((first, rest)) if !first.is_ascii() && !first.chars().any(char::is_alphanumeric) => rest,
+        _ => line,
+    }
+}
+
+/// `command` as one shell-like line, for logs and `--dry-run`
+pub fn command_line(command: &Command) -> String {
+    let argv: Vec<String> = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+    argv.join(" ")
+}
+
+/// Shows what is about to be executed under `--verbose`
+pub fn trace_command(command: &Command) {
+    tracing::debug!("   $ {}", command_line(command));
+}
+
+/// Set by commands whose stdout is machine-readable (e.g. `cask which`)
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+pub fn set_status_to_stderr(enabled: bool) {
+    STATUS_TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+pub fn status_to_stderr() -> bool {
+    STATUS_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Where streamed tool output (uv) should go so it follows status lines
+pub fn child_stdout() -> Stdio {
+    if quiet() {
+        Stdio::null()
+    } else if status_to_stderr() {
+        Stdio::from(std::io::stderr())
+    } else {
+        Stdio::inherit()
+    }
+}
+
+/// Installs the global subscriber that renders status events.
+/// `level` is an env-filter directive ("debug", "cask=trace"); RUST_LOG is the fallback,
+/// then whatever `--quiet` / `--verbose` imply.
+pub fn init_logging(level: Option<&str>, json: bool) -> Result<()> {
+    let default = if quiet() {
+        "error"
+    } else if verbose() {
+        "debug"
+    } else {
+        "info"
+    };
+    let filter = match level {
+        Some(directive) => EnvFilter::try_new(directive)
+            .map_err(|e| anyhow::anyhow!("Invalid --log-level '{}': {}", directive, e))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default)),
+    };
+
+    let output = tracing_subscriber::fmt::layer().with_writer(StatusWriter);
+    let output = if json {
+        colored::control::set_override(false); // No ANSI escapes inside JSON strings
+        output.json().flatten_event(true).with_current_span(false).with_span_list(false).boxed()
+    } else {
+        output.event_format(PlainFormat).boxed()
+    };
+    // Phase spans are for telemetry only; `filter` just decides what gets printed
+    let telemetry = telemetry::layer()?.map(|layer| layer.with_filter(LevelFilter::INFO));
+    tracing_subscriber::registry().with(output.with_filter(filter)).with(telemetry).init();
+    Ok(())
+}
+
+/// Pretty mode: just the message, exactly as the old println! output looked
+struct PlainFormat;
+
+impl<S, N> FormatEvent<S, N> for PlainFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, _ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut message = MessageField(String::new());
+        event.record(&mut message);
+        let text = if DECORATED.load(Ordering::Relaxed) { message.0.as_str() } else { strip_emoji(&message.0) };
+        if *event.metadata().level() >= Level::DEBUG {
+            writeln!(writer, "{}", text.dimmed())
+        } else {
+            writeln!(writer, "{}", text)
+        }
+    }
+}
+
+/// Extracts only the formatted message; structured fields are for --json-logs
+struct MessageField(String);
+
+impl Visit for MessageField {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+/// Warnings and errors go to stderr; everything else follows `status_to_stderr`.
+/// Either way, known secrets are scrubbed on the way out.
+struct StatusWriter;
+
+impl<'a> MakeWriter<'a> for StatusWriter {
+    type Writer = Redacting<Box<dyn io::Write + 'a>>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        if status_to_stderr() {
+            Redacting(Box::new(io::stderr()))
+        } else {
+            Redacting(Box::new(io::stdout()))
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        if *meta.level() <= Level::WARN {
+            Redacting(Box::new(io::stderr()))
+        } else {
+            self.make_writer()
+        }
+    }
+}
+
+/// Writer that runs `secrets::redact` over everything passing through.
+/// tracing hands over each formatted event in one write, so secrets are never split.
+struct Redacting<W>(W);
+
+impl<W: io::Write> io::Write for Redacting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = secrets::redact(&String::from_utf8_lossy(buf));
+        self.0.write_all(text.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Info-level status line (the emoji progress output); see `init_logging`
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        tracing::info!($($arg)*)
+    };
+}
@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::interrupt;
+
+/// How long the tree must be quiet before a burst of saves counts as one change
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// How often blocking waits check for Ctrl-C
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Directories whose churn never triggers a re-run
+const IGNORED_DIRS: [&str; 2] = [".venv", ".git"];
+
+/// Recursively watches a project root for edits
+pub struct ProjectWatcher {
+    _watcher: notify::RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// Extra roots to ignore (the holotree, when it lives inside the project)
+    ignored: Vec<PathBuf>,
+}
+
+impl ProjectWatcher {
+    pub fn new(root: &Path, ignored: Vec<PathBuf>) -> Result<Self> {
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).context("Failed to start file watcher")?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {:?}", root))?;
+        Ok(Self { _watcher: watcher, events, ignored })
+    }
+
+    /// Drops everything queued so far, e.g. files the payload itself wrote
+    pub fn discard_pending(&self) {
+        while self.events.try_recv().is_ok() {}
+    }
+
+    /// Blocks until a relevant change has settled and returns the touched paths.
+    /// Returns `None` once Ctrl-C is pressed.
+    pub fn next_change(&self) -> Option<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+        loop {
+            if interrupt::interrupted() {
+                return None;
+            }
+            // Poll until the first change, then until the tree has been quiet for DEBOUNCE
+            let timeout = if changed.is_empty() { POLL_INTERVAL } else { DEBOUNCE };
+            match self.events.recv_timeout(timeout) {
+                Ok(Ok(event)) if is_edit(&event.kind) => {
+                    changed.extend(event.paths.into_iter().filter(|p| !self.is_ignored(p)));
+                }
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) if !changed.is_empty() => {
+                    changed.sort();
+                    changed.dedup();
+                    return Some(changed);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        path.components().any(|c| IGNORED_DIRS.iter().any(|d| c.as_os_str() == *d))
+            || self.ignored.iter().any(|root| path.starts_with(root))
+    }
+}
+
+fn is_edit(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+}
@@ -0,0 +1,160 @@
+//! `cask.workspace.yaml`: the member projects of a monorepo, and defaults they inherit.
+//!
+//! ```yaml
+//! members:
+//!   - robots/invoices
+//!   - robots/onboarding
+//! defaults:
+//!   python: "3.12"
+//!   index_url: https://pypi.internal/simple
+//! ```
+//!
+//! A member's own config wins over the workspace defaults, which win over
+//! `$CASK_HOME/config.toml`.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+pub const WORKSPACE_FILE: &str = "cask.workspace.yaml";
+
+/// Blueprint keys a workspace may provide defaults for. All are plain values; paths and
+/// dependencies stay with the projects, which resolve them relative to themselves.
+const DEFAULT_KEYS: &[&str] = &[
+    "python",
+    "index_url",
+    "extra_index_urls",
+    "engine_version",
+    "require_hashes",
+    "uv_args",
+    "python_preference",
+    "python_mirror",
+    "venv_name",
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workspace {
+    /// Project directories, relative to the workspace file
+    members: Vec<PathBuf>,
+    #[serde(default)]
+    defaults: Mapping,
+    /// Directory holding the workspace file
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+/// One project of a workspace
+#[derive(Debug)]
+pub struct Member {
+    /// As listed in `members`, e.g. "robots/invoices"
+    pub path: PathBuf,
+    pub config: PathBuf,
+}
+
+impl Member {
+    /// The project directory
+    pub fn dir(&self) -> &Path {
+        self.config.parent().unwrap_or(Path::new("."))
+    }
+}
+
+impl Workspace {
+    pub fn load(file: &Path) -> Result<Self> {
+        let content = fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let mut workspace: Workspace =
+            serde_yaml::from_str(&content).with_context(|| format!("Invalid {}", file.display()))?;
+        workspace.root = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        for key in workspace.defaults.keys() {
+            let key = key.as_str().unwrap_or_default();
+            if !DEFAULT_KEYS.contains(&key) {
+                anyhow::bail!(
+                    "{}: '{}' can't be a workspace default (allowed: {})",
+                    file.display(), key, DEFAULT_KEYS.join(", ")
+                );
+            }
+        }
+        if let Some(Value::Number(python)) = workspace.defaults.get("python") {
+            // Unlike a blueprint, the raw text isn't recovered here: 3.10 would already be 3.1
+            anyhow::bail!("{}: quote the python default (\"{}\")", file.display(), python);
+        }
+        if workspace.members.is_empty() {
+            anyhow::bail!("{}: `members` is empty", file.display());
+        }
+        Ok(workspace)
+    }
+
+    /// The nearest workspace file in `dir` or its parents
+    pub fn find(dir: &Path) -> Result<Option<Self>> {
+        let dir = fs::canonicalize(dir)?;
+        match dir.ancestors().map(|d| d.join(WORKSPACE_FILE)).find(|f| f.is_file()) {
+            Some(file) => Ok(Some(Self::load(&file)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The workspace that lists the project of `config` as a member, if any
+    pub fn containing(config: &Path) -> Result<Option<Self>> {
+        let Ok(config) = fs::canonicalize(config) else { return Ok(None) };
+        let Some(dir) = config.parent() else { return Ok(None) };
+        let Some(workspace) = Self::find(dir)? else { return Ok(None) };
+        let listed = workspace.members.iter().any(|member| {
+            fs::canonicalize(workspace.root.join(member)).is_ok_and(|member| member == dir)
+        });
+        Ok(listed.then_some(workspace))
+    }
+
+    /// Every member with its config; a member without one is an error
+    pub fn members(&self) -> Result<Vec<Member>> {
+        self.members
+            .iter()
+            .map(|path| {
+                let dir = self.root.join(path);
+                let config = config::config_names(Path::new(config::DEFAULT_CONFIG))
+                    .into_iter()
+                    .map(|name| dir.join(name))
+                    .find(|candidate| candidate.is_file())
+                    .with_context(|| format!("Workspace member {} has no {}", path.display(), config::DEFAULT_CONFIG))?;
+                Ok(Member { path: path.clone(), config })
+            })
+            .collect()
+    }
+
+    /// A member by its path as listed, its directory name, or its project `name`
+    pub fn member(&self, name: &str) -> Result<Member> {
+        let members = self.members()?;
+        let names: Vec<String> = members.iter().map(|m| m.path.display().to_string()).collect();
+        let mut matches: Vec<Member> = Vec::new();
+        for member in members {
+            let project = config::Blueprint::load(&member.config).ok().and_then(|b| b.name);
+            let hit = member.path == Path::new(name)
+                || member.path.file_name().is_some_and(|dir| dir == name)
+                || project.as_deref() == Some(name);
+            if hit {
+                matches.push(member);
+            }
+        }
+        match matches.len() {
+            1 => Ok(matches.remove(0)),
+            0 => anyhow::bail!("No workspace member '{}' (members: {})", name, names.join(", ")),
+            _ => anyhow::bail!("'{}' matches several workspace members; use its path ({})", name, names.join(", ")),
+        }
+    }
+
+    /// Fills the keys a (fully merged) member blueprint leaves out, recording each in `defaulted`
+    pub fn apply(&self, layer: &mut Mapping, defaulted: &mut BTreeMap<&'static str, &'static str>) {
+        for key in DEFAULT_KEYS {
+            let Some(value) = self.defaults.get(*key) else { continue };
+            if !layer.contains_key(*key) {
+                layer.insert(Value::from(*key), value.clone());
+                defaulted.insert(key, "workspace");
+            }
+        }
+    }
+}